@@ -0,0 +1,8 @@
+-- per chat overrides of format policy, NULL means global one from env
+ALTER TABLE "chat"
+    ADD COLUMN max_height   INTEGER,
+    ADD COLUMN containers   VARCHAR,
+    ADD COLUMN vcodecs      VARCHAR,
+    ADD COLUMN acodecs      VARCHAR,
+    ADD COLUMN max_vbr      REAL,
+    ADD COLUMN max_fps      REAL;
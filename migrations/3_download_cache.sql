@@ -0,0 +1,10 @@
+CREATE TABLE "download_cache"
+(
+    id                  SERIAL  PRIMARY KEY,
+    extractor           VARCHAR NOT NULL,
+    video_id            VARCHAR NOT NULL,
+    quality             VARCHAR NOT NULL,
+    file_id             VARCHAR NOT NULL,
+
+    UNIQUE(extractor, video_id, quality)
+);
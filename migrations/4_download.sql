@@ -0,0 +1,23 @@
+CREATE TABLE "download"
+(
+    id                  SERIAL      PRIMARY KEY,
+    user_id             INTEGER,
+    chat_tg_id          BIGINT      NOT NULL,
+    url                 VARCHAR     NOT NULL,
+    extractor           VARCHAR,
+    video_id            VARCHAR,
+    format_ids          VARCHAR,
+    bytes               BIGINT,
+    elapsed_ms          BIGINT      NOT NULL,
+    status              VARCHAR     NOT NULL,
+    error               VARCHAR,
+    created_at          TIMESTAMPTZ NOT NULL DEFAULT now(),
+
+    FOREIGN KEY(user_id)    REFERENCES "user"(id)
+);
+
+CREATE INDEX idx_download_user_id
+    ON "download"(user_id);
+
+CREATE INDEX idx_download_created_at
+    ON "download"(created_at);
@@ -0,0 +1,10 @@
+-- per user and per chat download quotas, NULL means global one from env, 0 means unlimited
+ALTER TABLE "user"
+    ADD COLUMN quota_hour   INTEGER,
+    ADD COLUMN quota_day    INTEGER,
+    ADD COLUMN quota_bytes  BIGINT;
+
+ALTER TABLE "chat"
+    ADD COLUMN quota_hour   INTEGER,
+    ADD COLUMN quota_day    INTEGER,
+    ADD COLUMN quota_bytes  BIGINT;
@@ -0,0 +1,3 @@
+-- banned users are ignored by bot completely
+ALTER TABLE "user"
+    ADD COLUMN is_banned    BOOLEAN NOT NULL DEFAULT false;
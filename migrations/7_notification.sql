@@ -0,0 +1,12 @@
+-- admin DMs about pending requests, so all of them can be updated once request is decided
+CREATE TABLE "notification"
+(
+    id                  SERIAL  PRIMARY KEY,
+    kind                VARCHAR NOT NULL,
+    request_id          INTEGER NOT NULL,
+    chat_tg_id          BIGINT  NOT NULL,
+    message_id          INTEGER NOT NULL
+);
+
+CREATE INDEX idx_notification_kind_request_id
+    ON "notification"(kind, request_id);
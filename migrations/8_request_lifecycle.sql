@@ -0,0 +1,68 @@
+-- requests are kept with their status instead of being deleted, so users and
+-- admins may have any number of them over time, but only one pending at once
+ALTER TABLE "request"
+    DROP CONSTRAINT request_requested_by_key,
+    DROP CONSTRAINT request_approved_by_key;
+ALTER TABLE "request" RENAME COLUMN approved_by TO decided_by;
+ALTER TABLE "request"
+    ADD COLUMN status       VARCHAR     NOT NULL DEFAULT 'pending',
+    ADD COLUMN decided_at   TIMESTAMPTZ,
+    ADD COLUMN reason       VARCHAR,
+    ADD COLUMN created_at   TIMESTAMPTZ NOT NULL DEFAULT now();
+UPDATE "request" SET status = 'approved', decided_at = now() WHERE is_approved;
+
+ALTER TABLE "request_chat"
+    DROP CONSTRAINT request_chat_requested_by_key,
+    DROP CONSTRAINT request_chat_requested_for_key,
+    DROP CONSTRAINT request_chat_approved_by_key;
+ALTER TABLE "request_chat" RENAME COLUMN approved_by TO decided_by;
+ALTER TABLE "request_chat"
+    ADD COLUMN status       VARCHAR     NOT NULL DEFAULT 'pending',
+    ADD COLUMN decided_at   TIMESTAMPTZ,
+    ADD COLUMN reason       VARCHAR,
+    ADD COLUMN created_at   TIMESTAMPTZ NOT NULL DEFAULT now();
+UPDATE "request_chat" SET status = 'approved', decided_at = now() WHERE is_approved;
+
+DROP TRIGGER approve ON "request";
+DROP FUNCTION approve();
+DROP TRIGGER approve_chat ON "request_chat";
+DROP FUNCTION approve_chat();
+
+ALTER TABLE "request" DROP COLUMN is_approved;
+ALTER TABLE "request_chat" DROP COLUMN is_approved;
+
+CREATE UNIQUE INDEX idx_request_pending
+    ON "request"(requested_by) WHERE status = 'pending';
+
+CREATE UNIQUE INDEX idx_request_chat_pending
+    ON "request_chat"(requested_for) WHERE status = 'pending';
+
+CREATE FUNCTION approve()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF new.status = 'approved' THEN
+        UPDATE "user" SET can_download = true WHERE "user".id = new.requested_by;
+    END IF;
+    RETURN new;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER approve
+AFTER UPDATE OF status ON "request"
+FOR EACH ROW
+EXECUTE FUNCTION approve();
+
+CREATE FUNCTION approve_chat()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF new.status = 'approved' THEN
+        UPDATE "chat" SET can_download = true WHERE "chat".id = new.requested_for;
+    END IF;
+    RETURN new;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER approve_chat
+AFTER UPDATE OF status ON "request_chat"
+FOR EACH ROW
+EXECUTE FUNCTION approve_chat();
@@ -0,0 +1,3 @@
+-- delete link message after successful upload, needs delete rights in the chat
+ALTER TABLE "chat"
+    ADD COLUMN delete_link BOOLEAN NOT NULL DEFAULT false;
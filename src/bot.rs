@@ -1,12 +1,25 @@
 pub mod bot;
+pub mod callback;
+pub mod caption;
+pub mod clip;
+pub mod delete_link;
 pub mod dl;
+pub mod format;
+pub mod history;
+pub mod link;
 pub mod notify;
 pub mod op;
+pub mod playlist;
+pub mod progress;
+pub mod quality;
+pub mod queue;
+pub mod quota;
 pub mod request;
 pub mod request_chat;
 pub mod sanitize;
 pub mod start;
 pub mod types;
+pub mod users;
 pub mod version;
 
 #[macro_export]
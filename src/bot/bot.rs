@@ -4,23 +4,35 @@ use url::Url;
 use std::str::{self, FromStr};
 use std::time::Duration;
 use teloxide::dispatching::{dialogue, dialogue::InMemStorage, UpdateHandler};
-use teloxide::types::{InputFile, InputMediaVideo, Me, MessageKind, MessageNewChatMembers, UpdateKind};
+use teloxide::types::{InputFile, Me, MessageKind, MessageNewChatMembers, UpdateKind};
 use teloxide::{prelude::*, update_listeners::Polling, utils::command::BotCommands};
 use tracing::{event, Level};
 
 use super::start::handle_new_chat_member;
 use super::types::*;
 use super::version::cmd_version;
+use crate::db::user::find_user_by_tg_id;
 use crate::db::DbPool;
+use crate::dl::workspace::Workspace;
 use crate::util::{parse_env, unwrap_env};
 
-use super::dl::cmd_download;
-use super::op::cmd_op;
+use super::callback::handle_callback;
+use super::clip::cmd_clip;
+use super::delete_link::cmd_deletelink;
+use super::dl::{cmd_audio, cmd_cancel, cmd_download, handle_auto_download};
+use super::format::{cmd_format, cmd_setformat};
+use super::history::cmd_history;
+use super::link::{cmd_addlink, cmd_listlinks, cmd_rmlink, cmd_setlink};
+use super::op::{cmd_deop, cmd_op};
+use super::playlist::cmd_playlist;
+use super::queue::DownloadQueue;
+use super::quota::{cmd_quota, cmd_setquota};
 use super::request::{cmd_approve, cmd_decline, cmd_listrequests, cmd_request};
 use super::request_chat::{
     cmd_approve_chat, cmd_decline_chat, cmd_listrequests_chat, cmd_request_chat,
 };
 use super::start::{cmd_start, handle_my_chat_member};
+use super::users::{cmd_ban, cmd_grant, cmd_revoke, cmd_unban, cmd_users};
 
 pub async fn bot_main(db: DbPool) -> anyhow::Result<()> {
     event!(Level::INFO, "start");
@@ -34,8 +46,12 @@ pub async fn bot_main(db: DbPool) -> anyhow::Result<()> {
         .drop_pending_updates()
         .build();
 
+    Workspace::sweep();
+    let queue = DownloadQueue::from_env();
+    queue.start(bot.clone(), db.clone());
+
     Dispatcher::builder(bot, schema())
-        .dependencies(dptree::deps![db, InMemStorage::<State>::new()])
+        .dependencies(dptree::deps![db, queue, InMemStorage::<State>::new()])
         .enable_ctrlc_handler()
         .build()
         .dispatch_with_listener(
@@ -55,7 +71,17 @@ fn schema() -> UpdateHandler<HandlerErr> {
         .branch(case![Command::Version].endpoint(cmd_version))
         .branch(case![Command::Start].endpoint(cmd_start))
         .branch(case![Command::Download(url)].endpoint(cmd_download))
-        .branch(case![Command::OP].endpoint(cmd_op))
+        .branch(case![Command::Audio(url)].endpoint(cmd_audio))
+        .branch(case![Command::Playlist(text)].endpoint(cmd_playlist))
+        .branch(case![Command::Clip(text)].endpoint(cmd_clip))
+        .branch(case![Command::Cancel(id)].endpoint(cmd_cancel))
+        .branch(case![Command::OP(text)].endpoint(cmd_op))
+        .branch(case![Command::DeOP(text)].endpoint(cmd_deop))
+        .branch(case![Command::Grant(text)].endpoint(cmd_grant))
+        .branch(case![Command::Revoke(text)].endpoint(cmd_revoke))
+        .branch(case![Command::Ban(text)].endpoint(cmd_ban))
+        .branch(case![Command::Unban(text)].endpoint(cmd_unban))
+        .branch(case![Command::Users(text)].endpoint(cmd_users))
         .branch(case![Command::Request(text)].endpoint(cmd_request))
         .branch(case![Command::ListRequests].endpoint(cmd_listrequests))
         .branch(case![Command::Approve(text)].endpoint(cmd_approve))
@@ -63,17 +89,46 @@ fn schema() -> UpdateHandler<HandlerErr> {
         .branch(case![Command::RequestChat(text)].endpoint(cmd_request_chat))
         .branch(case![Command::ListRequestsChat].endpoint(cmd_listrequests_chat))
         .branch(case![Command::ApproveChat(text)].endpoint(cmd_approve_chat))
-        .branch(case![Command::DeclineChat(text)].endpoint(cmd_decline_chat));
+        .branch(case![Command::DeclineChat(text)].endpoint(cmd_decline_chat))
+        .branch(case![Command::AddLink(text)].endpoint(cmd_addlink))
+        .branch(case![Command::RmLink(text)].endpoint(cmd_rmlink))
+        .branch(case![Command::ListLinks].endpoint(cmd_listlinks))
+        .branch(case![Command::SetLink(text)].endpoint(cmd_setlink))
+        .branch(case![Command::Format].endpoint(cmd_format))
+        .branch(case![Command::SetFormat(text)].endpoint(cmd_setformat))
+        .branch(case![Command::DeleteLink(text)].endpoint(cmd_deletelink))
+        .branch(case![Command::History(text)].endpoint(cmd_history))
+        .branch(case![Command::Quota].endpoint(cmd_quota))
+        .branch(case![Command::SetQuota(text)].endpoint(cmd_setquota));
 
     let message_handler = Update::filter_message().branch(command_handler);
     let raw_message_handler = Update::filter_message().branch(dptree::endpoint(handle_message));
+    let callback_handler = Update::filter_callback_query().endpoint(handle_callback);
 
     dialogue::enter::<Update, InMemStorage<()>, (), _>()
+        .filter_async(not_banned)
         .branch(message_handler)
         .branch(raw_message_handler)
+        .branch(callback_handler)
         .endpoint(handle_update)
 }
 
+// banned users are ignored before any handler runs
+async fn not_banned(upd: Update, db: DbPool) -> bool {
+    let user = match upd.from() {
+        Some(user) => user,
+        None => return true,
+    };
+
+    match find_user_by_tg_id(&db, user.id.0 as i64).await {
+        Ok(user) => !matches!(user, Some(user) if user.is_banned),
+        Err(e) => {
+            event!(Level::ERROR, "ban check error {}", e);
+            true
+        }
+    }
+}
+
 async fn handle_update(_bot: Bot, upd: Update, db: DbPool) -> HandlerResult {
     match upd.kind {
         UpdateKind::MyChatMember(upd) => handle_my_chat_member(db, upd).await,
@@ -88,13 +143,14 @@ async fn handle_message(
     _dialogue: MyDialogue,
     msg: Message,
     db: DbPool,
+    queue: DownloadQueue,
     me: Me,
 ) -> HandlerResult {
     match msg.kind {
         MessageKind::NewChatMembers(MessageNewChatMembers { new_chat_members }) => {
             handle_new_chat_member(bot, &msg.chat, new_chat_members, db, me).await?
         }
-        MessageKind::Common(_) => (),
+        MessageKind::Common(_) => handle_auto_download(msg, db, queue).await?,
         _ => {
             dbg!(msg);
         }
@@ -112,9 +168,21 @@ enum Command {
 
     #[command(alias = "dl")]
     Download(String),
+    #[command(alias = "mp3")]
+    Audio(String),
+    Playlist(String),
+    Clip(String),
+    Cancel(String),
 
     #[command(alias = "op")]
-    OP,
+    OP(String),
+    #[command(alias = "deop")]
+    DeOP(String),
+    Grant(String),
+    Revoke(String),
+    Ban(String),
+    Unban(String),
+    Users(String),
 
     Request(String),
     ListRequests,
@@ -129,6 +197,18 @@ enum Command {
     ApproveChat(String),
     #[command(alias = "decline_chat")]
     DeclineChat(String),
+
+    AddLink(String),
+    RmLink(String),
+    ListLinks,
+    SetLink(String),
+    Format,
+    SetFormat(String),
+    DeleteLink(String),
+
+    History(String),
+    Quota,
+    SetQuota(String),
 }
 
 async fn cmd_test(bot: Bot, msg: Message, _db: DbPool) -> HandlerResult {
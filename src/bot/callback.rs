@@ -0,0 +1,32 @@
+use teloxide::prelude::*;
+use tracing::{event, Level};
+
+use super::notify::{handle_request_callback, parse_request_data};
+use super::quality::{handle_quality_callback, parse_quality_data};
+use super::queue::DownloadQueue;
+use super::types::HandlerResult;
+use crate::db::DbPool;
+
+// inline keyboard buttons of all kinds end up here, routed by callback data
+pub async fn handle_callback(
+    bot: Bot,
+    q: CallbackQuery,
+    db: DbPool,
+    queue: DownloadQueue,
+) -> HandlerResult {
+    let data = match q.data.as_deref() {
+        Some(data) => data,
+        None => return Ok(()),
+    };
+
+    if let Some(quality) = parse_quality_data(data) {
+        return handle_quality_callback(bot, q, quality, db, queue).await;
+    }
+    if let Some((kind, decision, id)) = parse_request_data(data) {
+        return handle_request_callback(bot, q, kind, decision, id, db).await;
+    }
+
+    event!(Level::WARN, "unknown callback data {}", data);
+    bot.answer_callback_query(q.id).await?;
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use rust_i18n::t;
+use teloxide::types::User;
+
+use crate::dl::yt_dlp::YtDlpInfo;
+
+// telegram limit for media captions
+const CAPTION_LIMIT: usize = 1024;
+
+pub fn requester_name(user: &User) -> String {
+    match &user.username {
+        Some(username) => format!("@{}", username),
+        None => user.first_name.clone(),
+    }
+}
+
+// title, uploader, source link and who asked for it
+pub fn video_caption(info: &YtDlpInfo, url: &str, requester: Option<&str>) -> String {
+    let mut lines = vec![info.title.clone()];
+    if let Some(uploader) = &info.uploader {
+        lines.push(uploader.clone());
+    }
+    lines.push(info.webpage_url.as_deref().unwrap_or(url).to_string());
+    if let Some(requester) = requester {
+        lines.push(t!("caption_requested_by", user = requester).to_string());
+    }
+
+    truncate_caption(lines.join("\n"))
+}
+
+pub fn truncate_caption(caption: String) -> String {
+    if caption.chars().count() <= CAPTION_LIMIT {
+        return caption;
+    }
+    let mut truncated: String = caption.chars().take(CAPTION_LIMIT - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{truncate_caption, video_caption, CAPTION_LIMIT};
+    use crate::dl::yt_dlp::YtDlpInfo;
+
+    fn info() -> YtDlpInfo {
+        let json = r#"{
+            "id": "abc",
+            "title": "Title",
+            "uploader": "Channel",
+            "webpage_url": "https://www.youtube.com/watch?v=abc",
+            "formats": []
+        }"#;
+        YtDlpInfo::parse(json.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_video_caption() {
+        let caption = video_caption(&info(), "https://youtu.be/abc", None);
+        assert_eq!(
+            caption,
+            "Title\nChannel\nhttps://www.youtube.com/watch?v=abc"
+        );
+
+        let mut info = info();
+        info.uploader = None;
+        info.webpage_url = None;
+        let caption = video_caption(&info, "https://youtu.be/abc", Some("@someone"));
+        assert_eq!(
+            caption,
+            "Title\nhttps://youtu.be/abc\nRequested by @someone"
+        );
+    }
+
+    #[test]
+    fn test_truncate_caption() {
+        assert_eq!(truncate_caption("short".to_string()), "short");
+
+        let long = "я".repeat(CAPTION_LIMIT + 10);
+        let truncated = truncate_caption(long);
+        assert_eq!(truncated.chars().count(), CAPTION_LIMIT);
+        assert!(truncated.ends_with('…'));
+    }
+}
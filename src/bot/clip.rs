@@ -0,0 +1,174 @@
+use teloxide::prelude::*;
+
+use super::dl::enqueue_download;
+use super::queue::{DownloadQueue, JobKind};
+use super::sanitize::parse_url;
+use super::types::HandlerResult;
+use crate::db::DbPool;
+use crate::dl::clip::Clip;
+use crate::reply_i18n_and_return;
+
+fn parse_colon_timestamp(text: &str) -> Option<u32> {
+    let parts: Vec<&str> = text.split(':').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+
+    let mut seconds: u32 = 0;
+    for (i, part) in parts.into_iter().enumerate() {
+        let value: u32 = part.parse().ok()?;
+        // only the leading part may go over 59
+        if i > 0 && value > 59 {
+            return None;
+        }
+        seconds = seconds.checked_mul(60)?.checked_add(value)?;
+    }
+
+    Some(seconds)
+}
+
+fn parse_unit_timestamp(text: &str) -> Option<u32> {
+    let mut seconds: u32 = 0;
+    let mut number = String::new();
+    let mut last_unit = u32::MAX;
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let unit = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        // units go from hours to seconds, each one once
+        if number.is_empty() || unit >= last_unit {
+            return None;
+        }
+        let value: u32 = number.parse().ok()?;
+        seconds = seconds.checked_add(value.checked_mul(unit)?)?;
+        number.clear();
+        last_unit = unit;
+    }
+    if !number.is_empty() {
+        return None;
+    }
+
+    Some(seconds)
+}
+
+// 83, 1:23, 1:02:03, 83s, 1m23s, 1h2m
+pub fn parse_timestamp(text: &str) -> Option<u32> {
+    if text.is_empty() {
+        return None;
+    }
+    if let Ok(seconds) = text.parse() {
+        return Some(seconds);
+    }
+    if text.contains(':') {
+        return parse_colon_timestamp(text);
+    }
+    parse_unit_timestamp(text)
+}
+
+// youtube links shared from a moment have it as ?t=83 or ?t=1m23s
+fn url_start(url: &str) -> Option<u32> {
+    let url = parse_url(url)?;
+    let (_, t) = url.query_pairs().find(|(key, _)| key == "t")?;
+    parse_timestamp(&t)
+}
+
+// /clip <url> [start] [end], start is taken from the link if not given
+fn parse_args(text: &str) -> Option<(&str, Clip)> {
+    let mut args = text.split_whitespace();
+    let url = args.next()?;
+    let start = match args.next() {
+        Some(start) => parse_timestamp(start)?,
+        None => url_start(url)?,
+    };
+    let end = match args.next() {
+        Some(end) => Some(parse_timestamp(end)?),
+        None => None,
+    };
+    if args.next().is_some() || end.is_some_and(|end| end <= start) {
+        return None;
+    }
+
+    Some((url, Clip { start, end }))
+}
+
+pub async fn cmd_clip(
+    bot: Bot,
+    msg: Message,
+    text: String,
+    db: DbPool,
+    queue: DownloadQueue,
+) -> HandlerResult {
+    let (url, clip) = match parse_args(&text) {
+        Some(args) => args,
+        None => {
+            reply_i18n_and_return!(bot, msg.chat.id, "clip_usage");
+        }
+    };
+
+    let url = url.to_string();
+    enqueue_download(bot, msg, url, JobKind::Clip(clip), db, queue).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_args, parse_timestamp};
+    use crate::dl::clip::Clip;
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("83"), Some(83));
+        assert_eq!(parse_timestamp("1:23"), Some(83));
+        assert_eq!(parse_timestamp("1:02:03"), Some(3723));
+        assert_eq!(parse_timestamp("90:00"), Some(5400));
+        assert_eq!(parse_timestamp("83s"), Some(83));
+        assert_eq!(parse_timestamp("1m23s"), Some(83));
+        assert_eq!(parse_timestamp("1h2m"), Some(3720));
+        assert_eq!(parse_timestamp("1h2m3s"), Some(3723));
+
+        assert_eq!(parse_timestamp(""), None);
+        assert_eq!(parse_timestamp("1:75"), None);
+        assert_eq!(parse_timestamp("1:2:3:4"), None);
+        assert_eq!(parse_timestamp("1:"), None);
+        assert_eq!(parse_timestamp("2m1h"), None);
+        assert_eq!(parse_timestamp("1m1m"), None);
+        assert_eq!(parse_timestamp("1h2"), None);
+        assert_eq!(parse_timestamp("m"), None);
+        assert_eq!(parse_timestamp("1d"), None);
+        assert_eq!(parse_timestamp("-5"), None);
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let url = "https://www.youtube.com/watch?v=00000000000";
+        let clip = |start, end| Clip { start, end };
+        assert_eq!(
+            parse_args(&format!("{} 1:23 1:43", url)),
+            Some((url, clip(83, Some(103))))
+        );
+        assert_eq!(
+            parse_args(&format!("{} 1h2m", url)),
+            Some((url, clip(3720, None)))
+        );
+        assert_eq!(parse_args(url), None);
+        assert_eq!(parse_args(&format!("{} 1:43 1:23", url)), None);
+        assert_eq!(parse_args(&format!("{} 1 2 3", url)), None);
+
+        let url = "https://youtu.be/00000000000?t=83";
+        assert_eq!(parse_args(url), Some((url, clip(83, None))));
+        assert_eq!(
+            parse_args(&format!("{} 10 20", url)),
+            Some((url, clip(10, Some(20))))
+        );
+
+        let url = "https://www.youtube.com/watch?v=00000000000&t=1m23s";
+        assert_eq!(parse_args(url), Some((url, clip(83, None))));
+    }
+}
@@ -0,0 +1,62 @@
+use rust_i18n::t;
+use teloxide::prelude::*;
+use teloxide::types::Me;
+use tracing::{event, Level};
+
+use super::link::parse_switch;
+use super::types::HandlerResult;
+use crate::db::chat::{find_or_create_chat, update_chat_delete_link};
+use crate::db::user::find_or_create_user;
+use crate::db::DbPool;
+use crate::reply_i18n_and_return;
+
+// /deletelink <on|off> - delete link message once the video is posted
+pub async fn cmd_deletelink(
+    bot: Bot,
+    msg: Message,
+    text: String,
+    db: DbPool,
+    me: Me,
+) -> HandlerResult {
+    if let Some(user) = msg.from() {
+        let user = find_or_create_user(&db, user).await?;
+        if !user.is_admin {
+            reply_i18n_and_return!(bot, msg.chat.id, "not_an_admin");
+        }
+        if msg.chat.is_private() {
+            reply_i18n_and_return!(bot, msg.chat.id, "deletelink_private");
+        }
+
+        let delete_link = match parse_switch(text.trim()) {
+            Some(delete_link) => delete_link,
+            None => {
+                reply_i18n_and_return!(bot, msg.chat.id, "deletelink_usage");
+            }
+        };
+        if delete_link {
+            let member = bot.get_chat_member(msg.chat.id, me.id).await?;
+            if !member.kind.can_delete_messages() {
+                reply_i18n_and_return!(bot, msg.chat.id, "deletelink_no_rights");
+            }
+        }
+
+        let chat = find_or_create_chat(&db, &msg.chat).await?;
+        update_chat_delete_link(&db, chat.id, delete_link).await?;
+        event!(
+            Level::INFO,
+            "delete link of {} set to {} by {}",
+            chat,
+            delete_link,
+            user
+        );
+
+        let key = if delete_link {
+            "deletelink_on"
+        } else {
+            "deletelink_off"
+        };
+        bot.send_message(msg.chat.id, t!(key)).await?;
+    }
+
+    Ok(())
+}
@@ -1,32 +1,722 @@
+use rust_i18n::t;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
 use teloxide::prelude::*;
-use teloxide::types::InputFile;
+use teloxide::types::{InputFile, InputMedia, InputMediaPhoto, InputMediaVideo, MessageId, UserId};
+use teloxide::RequestError;
 use tracing::{event, Level};
 
+use super::caption::{requester_name, truncate_caption, video_caption};
+use super::format::job_policy;
+use super::playlist::finish_item;
+use super::progress::{progress_text, report_progress};
+use super::quality::show_quality_picker;
+use super::queue::{CancelError, DownloadQueue, Job, JobKind, QueueError};
+use super::quota::quota_denied;
+use super::sanitize::{extract_url, parse_url};
 use super::types::HandlerResult;
-use crate::dl::delete_if_exists;
-use crate::dl::download;
+use crate::db::cache::{delete_cached, find_cached, save_cached, CacheKey};
+use crate::db::chat::find_or_create_chat;
+use crate::db::download::{create_download, DownloadRecord, DownloadStatus};
+use crate::db::link::find_link;
+use crate::db::user::find_or_create_user;
+use crate::db::DbPool;
+use crate::dl::progress::{progress_channel, Progress};
+use crate::dl::workspace::Workspace;
+use crate::dl::yt_dlp::{VideoQuality, YtDlpInfo};
+use crate::dl::{
+    download, download_audio, download_gallery, download_video_thumbnail, load_info,
+    select_audio_format, select_formats, AudioDownload, DownloadContext, DownloadError,
+    GalleryItem,
+};
+use crate::{parse_integer, reply_i18n_and_return};
 
-async fn bot_download(bot: Bot, msg: Message, url: String) -> HandlerResult {
-    let output_path = match download(url.as_str()).await {
-        Ok(path) => path,
+// telegram takes up to 10 photos and videos in one album
+const ALBUM_SIZE: usize = 10;
+
+pub fn download_error_text(e: &DownloadError) -> String {
+    match e {
+        DownloadError::Cancelled => t!("download_cancelled").to_string(),
+        DownloadError::Timeout => t!("download_timed_out").to_string(),
+        DownloadError::TooLong => t!("video_too_long").to_string(),
+        DownloadError::TooLarge => t!("video_too_large").to_string(),
+        DownloadError::TooManyItems => t!("gallery_too_many_items").to_string(),
+        DownloadError::ClipOutOfRange => t!("clip_out_of_range").to_string(),
+        DownloadError::Playlist => t!("link_is_playlist").to_string(),
+        _ => e.to_string(),
+    }
+}
+
+enum JobError {
+    Download(DownloadError),
+    Request(RequestError),
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Download(e) => write!(f, "{}", e),
+            Self::Request(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<DownloadError> for JobError {
+    fn from(value: DownloadError) -> Self {
+        Self::Download(value)
+    }
+}
+
+impl From<RequestError> for JobError {
+    fn from(value: RequestError) -> Self {
+        Self::Request(value)
+    }
+}
+
+fn cache_key(info: &YtDlpInfo, quality: String) -> CacheKey<'_> {
+    CacheKey {
+        extractor: info.extractor_key.as_deref().unwrap_or("generic"),
+        video_id: &info.id,
+        quality,
+    }
+}
+
+// cache is only an optimization, so its errors don't fail the job
+async fn find_cached_or_log(db: &DbPool, key: &CacheKey<'_>) -> Option<String> {
+    match find_cached(db, key).await {
+        Ok(file_id) => file_id,
         Err(e) => {
-            event!(Level::ERROR, "{}", e.to_string());
-            bot.send_message(msg.chat.id, e.to_string()).await?;
+            event!(Level::ERROR, "cache lookup error {}", e);
+            None
+        }
+    }
+}
+
+async fn save_cached_or_log(db: &DbPool, key: &CacheKey<'_>, file_id: &str) {
+    if let Err(e) = save_cached(db, key, file_id).await {
+        event!(Level::ERROR, "cache save error {}", e);
+    }
+}
+
+// file_id may become invalid, for example if bot api server was changed
+async fn invalidate_cached(db: &DbPool, key: &CacheKey<'_>, e: RequestError) {
+    event!(
+        Level::WARN,
+        "cached {} {} failed to send - {}",
+        key.video_id,
+        key.quality,
+        e
+    );
+    if let Err(e) = delete_cached(db, key).await {
+        event!(Level::ERROR, "cache delete error {}", e);
+    }
+}
+
+fn files_size<P: AsRef<Path>>(paths: &[P]) -> i64 {
+    paths
+        .iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|metadata| metadata.len() as i64)
+        .sum()
+}
+
+async fn save_history(
+    db: &DbPool,
+    msg: &Message,
+    mut record: DownloadRecord,
+    status: DownloadStatus,
+    error: Option<&str>,
+) {
+    if let Some(user) = msg.from() {
+        match find_or_create_user(db, user).await {
+            Ok(user) => record.user_id = Some(user.id),
+            Err(e) => event!(Level::ERROR, "history user error {}", e),
+        }
+    }
+
+    if let Err(e) = create_download(db, &record, status, error).await {
+        event!(Level::ERROR, "history save error {}", e);
+    }
+}
+
+// where results go, uploads reply to the link message unless it gets deleted after
+struct UploadTarget {
+    chat_id: ChatId,
+    reply_to: Option<MessageId>,
+    requester: Option<String>,
+}
+
+impl UploadTarget {
+    fn new(msg: &Message, delete_link: bool) -> Self {
+        // with link message gone, caption tells who asked for it
+        let requester = match msg.from() {
+            Some(user) if delete_link => Some(requester_name(user)),
+            _ => None,
+        };
+        Self {
+            chat_id: msg.chat.id,
+            reply_to: (!delete_link).then_some(msg.id),
+            requester,
+        }
+    }
+}
+
+// private chats have no settings, there is nothing to clean up there
+async fn delete_link_enabled(db: &DbPool, msg: &Message) -> bool {
+    if msg.chat.is_private() {
+        return false;
+    }
+    match find_or_create_chat(db, &msg.chat).await {
+        Ok(chat) => chat.delete_link,
+        Err(e) => {
+            event!(Level::ERROR, "chat settings error {}", e);
+            false
+        }
+    }
+}
+
+async fn upload_video(
+    bot: &Bot,
+    db: &DbPool,
+    target: &UploadTarget,
+    url: &str,
+    quality: Option<&VideoQuality>,
+    ctx: &DownloadContext,
+    record: &mut DownloadRecord,
+) -> Result<DownloadStatus, JobError> {
+    let info = load_info(url, ctx).await?;
+    record.extractor = info.extractor_key.clone();
+    record.video_id = Some(info.id.clone());
+    if info.is_gallery() {
+        return upload_gallery(bot, target, url, &info, ctx, record).await;
+    }
+    let selection = select_formats(&info, quality, ctx)?;
+    record.format_ids = Some(selection.id());
+    let caption = video_caption(&info, url, target.requester.as_deref());
+    let chat_id = target.chat_id;
+
+    // clips of a video are cached apart from the whole of it
+    let format = match ctx.clip {
+        Some(clip) => format!("{} clip {}", selection.id(), clip),
+        None => selection.id(),
+    };
+    let key = cache_key(&info, format);
+    if let Some(file_id) = find_cached_or_log(db, &key).await {
+        ctx.progress.send_replace(Progress::Uploading);
+        let mut request = bot
+            .send_video(chat_id, InputFile::file_id(file_id))
+            .caption(&caption);
+        if let Some(reply_to) = target.reply_to {
+            request = request
+                .reply_to_message_id(reply_to)
+                .allow_sending_without_reply(true);
+        }
+        match request.await {
+            Ok(_) => return Ok(DownloadStatus::Cached),
+            Err(e) => invalidate_cached(db, &key, e).await,
+        }
+    }
+
+    let parts = download(url, &info, &selection, ctx).await?;
+    record.bytes = Some(files_size(&parts));
+    let thumbnail = download_video_thumbnail(url, &info, ctx).await?;
+
+    ctx.progress.send_replace(Progress::Uploading);
+    let total = parts.len();
+    for (i, part) in parts.iter().enumerate() {
+        let mut request = bot
+            .send_video(chat_id, InputFile::file(part))
+            .supports_streaming(true);
+        if total > 1 {
+            let part = t!(
+                "video_part",
+                part = (i + 1).to_string(),
+                total = total.to_string()
+            );
+            request = request.caption(truncate_caption(format!("{}\n{}", caption, part)));
+        } else {
+            request = request.caption(&caption);
+            // parts are cut by size, so only whole video has known duration
+            if let Some(duration) = ctx.duration(&info) {
+                request = request.duration(duration as u32);
+            }
+        }
+        if let (Some(width), Some(height)) = (selection.width(), selection.height()) {
+            request = request.width(width as u32).height(height as u32);
+        }
+        if let Some(thumbnail) = &thumbnail {
+            request = request.thumbnail(InputFile::file(thumbnail));
+        }
+        if let Some(reply_to) = target.reply_to {
+            request = request
+                .reply_to_message_id(reply_to)
+                .allow_sending_without_reply(true);
+        }
+        let sent = request.await?;
+
+        // split videos are rare, not worth caching
+        if total == 1 {
+            if let Some(video) = sent.video() {
+                save_cached_or_log(db, &key, &video.file.id).await;
+            }
+        }
+    }
+
+    Ok(DownloadStatus::Done)
+}
+
+async fn send_audio_download(
+    bot: &Bot,
+    target: &UploadTarget,
+    audio: AudioDownload,
+    caption: Option<String>,
+) -> Result<Message, RequestError> {
+    let mut request = bot
+        .send_audio(target.chat_id, InputFile::file(&audio.path))
+        .title(audio.title);
+    if let Some(performer) = audio.performer {
+        request = request.performer(performer);
+    }
+    if let Some(duration) = audio.duration {
+        request = request.duration(duration as u32);
+    }
+    if let Some(caption) = caption {
+        request = request.caption(caption);
+    }
+    if let Some(reply_to) = target.reply_to {
+        request = request
+            .reply_to_message_id(reply_to)
+            .allow_sending_without_reply(true);
+    }
+    request.await
+}
+
+fn gallery_media(item: &GalleryItem, caption: Option<String>) -> InputMedia {
+    match item {
+        GalleryItem::Photo(path) => {
+            let mut photo = InputMediaPhoto::new(InputFile::file(path));
+            if let Some(caption) = caption {
+                photo = photo.caption(caption);
+            }
+            InputMedia::Photo(photo)
+        }
+        GalleryItem::Video(path) => {
+            let mut video = InputMediaVideo::new(InputFile::file(path)).supports_streaming(true);
+            if let Some(caption) = caption {
+                video = video.caption(caption);
+            }
+            InputMedia::Video(video)
+        }
+    }
+}
+
+async fn send_gallery_item(
+    bot: &Bot,
+    target: &UploadTarget,
+    item: &GalleryItem,
+    caption: Option<String>,
+) -> Result<(), RequestError> {
+    match item {
+        GalleryItem::Photo(path) => {
+            let mut request = bot.send_photo(target.chat_id, InputFile::file(path));
+            if let Some(caption) = caption {
+                request = request.caption(caption);
+            }
+            if let Some(reply_to) = target.reply_to {
+                request = request
+                    .reply_to_message_id(reply_to)
+                    .allow_sending_without_reply(true);
+            }
+            request.await?;
+        }
+        GalleryItem::Video(path) => {
+            let mut request = bot
+                .send_video(target.chat_id, InputFile::file(path))
+                .supports_streaming(true);
+            if let Some(caption) = caption {
+                request = request.caption(caption);
+            }
+            if let Some(reply_to) = target.reply_to {
+                request = request
+                    .reply_to_message_id(reply_to)
+                    .allow_sending_without_reply(true);
+            }
+            request.await?;
+        }
+    }
+    Ok(())
+}
+
+// albums can't be resent by a single file id, so galleries are not cached
+async fn upload_gallery(
+    bot: &Bot,
+    target: &UploadTarget,
+    url: &str,
+    info: &YtDlpInfo,
+    ctx: &DownloadContext,
+    record: &mut DownloadRecord,
+) -> Result<DownloadStatus, JobError> {
+    let gallery = download_gallery(url, info, ctx).await?;
+    let mut paths: Vec<&str> = gallery
+        .items
+        .iter()
+        .map(|item| match item {
+            GalleryItem::Photo(path) | GalleryItem::Video(path) => path.as_str(),
+        })
+        .collect();
+    if let Some(audio) = &gallery.audio {
+        paths.push(audio.path.as_str());
+    }
+    record.bytes = Some(files_size(&paths));
+
+    ctx.progress.send_replace(Progress::Uploading);
+    // caption goes on the first item, so telegram shows it under the album
+    let mut caption = Some(video_caption(info, url, target.requester.as_deref()));
+    for chunk in gallery.items.chunks(ALBUM_SIZE) {
+        // albums need at least two items
+        if let [item] = chunk {
+            send_gallery_item(bot, target, item, caption.take()).await?;
+            continue;
+        }
+
+        let media: Vec<InputMedia> = chunk
+            .iter()
+            .map(|item| gallery_media(item, caption.take()))
+            .collect();
+        let mut request = bot.send_media_group(target.chat_id, media);
+        if let Some(reply_to) = target.reply_to {
+            request = request
+                .reply_to_message_id(reply_to)
+                .allow_sending_without_reply(true);
+        }
+        request.await?;
+    }
+
+    if let Some(audio) = gallery.audio {
+        send_audio_download(bot, target, audio, None).await?;
+    }
+
+    Ok(DownloadStatus::Done)
+}
+
+async fn upload_audio(
+    bot: &Bot,
+    db: &DbPool,
+    target: &UploadTarget,
+    url: &str,
+    ctx: &DownloadContext,
+    record: &mut DownloadRecord,
+) -> Result<DownloadStatus, JobError> {
+    let info = load_info(url, ctx).await?;
+    record.extractor = info.extractor_key.clone();
+    record.video_id = Some(info.id.clone());
+    let af = select_audio_format(&info, ctx)?;
+    record.format_ids = Some(af.format_id.clone());
+
+    let caption = target
+        .requester
+        .as_ref()
+        .map(|requester| t!("caption_requested_by", user = requester).to_string());
+    let chat_id = target.chat_id;
+
+    let key = cache_key(&info, format!("audio {}", af.format_id));
+    if let Some(file_id) = find_cached_or_log(db, &key).await {
+        ctx.progress.send_replace(Progress::Uploading);
+        let mut request = bot.send_audio(chat_id, InputFile::file_id(file_id));
+        if let Some(caption) = &caption {
+            request = request.caption(caption);
+        }
+        if let Some(reply_to) = target.reply_to {
+            request = request
+                .reply_to_message_id(reply_to)
+                .allow_sending_without_reply(true);
+        }
+        match request.await {
+            Ok(_) => return Ok(DownloadStatus::Cached),
+            Err(e) => invalidate_cached(db, &key, e).await,
+        }
+    }
+
+    let audio = download_audio(url, &info, af, ctx).await?;
+    record.bytes = Some(files_size(&[audio.path.as_str()]));
+
+    ctx.progress.send_replace(Progress::Uploading);
+    let sent = send_audio_download(bot, target, audio, caption).await?;
+
+    if let Some(audio) = sent.audio() {
+        save_cached_or_log(db, &key, &audio.file.id).await;
+    }
+
+    Ok(DownloadStatus::Done)
+}
+
+pub async fn bot_download(bot: Bot, db: DbPool, job: Job, queue: DownloadQueue) -> HandlerResult {
+    let Job {
+        id,
+        kind,
+        policy,
+        msg,
+        url,
+        cancel,
+        playlist,
+    } = job;
+    // cancelled playlist items are left in queue only to report their result
+    if cancel.is_cancelled() {
+        if let Some(item) = &playlist {
+            finish_item(&bot, item, DownloadStatus::Cancelled).await?;
+        }
+        return Ok(());
+    }
+    // playlist is checked against quota as a whole when queued, but
+    // downloads finished since then count as well
+    if let Some(item) = &playlist {
+        if let Some(reason) = quota_denied(&db, &msg, &queue).await? {
+            event!(Level::INFO, "job {} denied by {}", id, reason);
+            finish_item(&bot, item, DownloadStatus::Cancelled).await?;
             return Ok(());
         }
+    }
+    let workspace = Workspace::for_job(id)?;
+
+    let (progress, rx) = progress_channel();
+    let status = bot
+        .send_message(msg.chat.id, progress_text(Progress::FetchingInfo))
+        .reply_to_message_id(msg.id)
+        .allow_sending_without_reply(true)
+        .await?;
+    let reporter = tokio::spawn(report_progress(bot.clone(), status.chat.id, status.id, rx));
+
+    let clip = match &kind {
+        JobKind::Clip(clip) => Some(*clip),
+        _ => None,
+    };
+    let ctx = DownloadContext {
+        progress,
+        cancel,
+        limits: queue.limits(),
+        policy,
+        workspace,
+        clip,
+    };
+    let started = Instant::now();
+    let mut record = DownloadRecord {
+        chat_tg_id: msg.chat.id.0,
+        url: url.clone(),
+        ..Default::default()
+    };
+    // playlist message is shared by all of its items, so it stays
+    let delete_link = playlist.is_none() && delete_link_enabled(&db, &msg).await;
+    let target = UploadTarget::new(&msg, delete_link);
+    let res = match kind {
+        JobKind::Video(quality) => {
+            let quality = quality.as_ref();
+            upload_video(&bot, &db, &target, &url, quality, &ctx, &mut record).await
+        }
+        JobKind::Clip(_) => upload_video(&bot, &db, &target, &url, None, &ctx, &mut record).await,
+        JobKind::Audio => upload_audio(&bot, &db, &target, &url, &ctx, &mut record).await,
+    };
+    reporter.abort();
+
+    record.elapsed_ms = started.elapsed().as_millis() as i64;
+    let (history_status, error) = match &res {
+        Ok(history_status) => (*history_status, None),
+        Err(JobError::Download(DownloadError::Cancelled)) => (DownloadStatus::Cancelled, None),
+        Err(e) => (DownloadStatus::Failed, Some(e.to_string())),
+    };
+    save_history(&db, &msg, record, history_status, error.as_deref()).await;
+    if let Some(item) = &playlist {
+        if let Err(e) = finish_item(&bot, item, history_status).await {
+            event!(Level::ERROR, "playlist summary error {}", e);
+        }
+    }
+
+    match res {
+        Ok(_) => {
+            bot.delete_message(status.chat.id, status.id).await?;
+            // rights could be taken away after the setting was enabled
+            if delete_link {
+                if let Err(e) = bot.delete_message(msg.chat.id, msg.id).await {
+                    event!(Level::WARN, "delete link message error {}", e);
+                }
+            }
+            Ok(())
+        }
+        Err(JobError::Download(e)) => {
+            event!(Level::ERROR, "{}", e.to_string());
+            bot.edit_message_text(status.chat.id, status.id, download_error_text(&e))
+                .await?;
+            Ok(())
+        }
+        Err(JobError::Request(e)) => {
+            bot.edit_message_text(status.chat.id, status.id, e.to_string())
+                .await?;
+            Err(Box::new(e))
+        }
+    }
+}
+
+pub async fn can_download(db: &DbPool, msg: &Message) -> Result<bool, sqlx::Error> {
+    if !msg.chat.is_private() {
+        let chat = find_or_create_chat(db, &msg.chat).await?;
+        if chat.can_download {
+            return Ok(true);
+        }
+    }
+
+    if let Some(user) = msg.from() {
+        let user = find_or_create_user(db, user).await?;
+        return Ok(user.can_download);
+    }
+
+    Ok(false)
+}
+
+// i18n key of the reason why url can't be downloaded
+pub async fn download_denied(
+    db: &DbPool,
+    msg: &Message,
+    url: &str,
+    queue: &DownloadQueue,
+) -> Result<Option<&'static str>, sqlx::Error> {
+    if !can_download(db, msg).await? {
+        return Ok(Some("no_download_permission"));
+    }
+
+    if let Some(parsed) = parse_url(url) {
+        if let Some(link) = find_link(db, &parsed).await? {
+            if !link.download_allowed {
+                return Ok(Some("link_download_not_allowed"));
+            }
+        }
+    }
+
+    quota_denied(db, msg, queue).await
+}
+
+pub fn enqueue_text(res: &Result<(i32, usize), QueueError>) -> String {
+    match res {
+        Ok((id, position)) => t!(
+            "download_queued",
+            id = id.to_string(),
+            position = position.to_string()
+        )
+        .to_string(),
+        Err(QueueError::UserLimit) => t!("queue_user_limit").to_string(),
+        Err(QueueError::ChatLimit) => t!("queue_chat_limit").to_string(),
+    }
+}
+
+pub async fn enqueue_download(
+    bot: Bot,
+    msg: Message,
+    url: String,
+    kind: JobKind,
+    db: DbPool,
+    queue: DownloadQueue,
+) -> HandlerResult {
+    if let Some(reason) = download_denied(&db, &msg, &url, &queue).await? {
+        reply_i18n_and_return!(bot, msg.chat.id, reason);
+    }
+
+    let policy = job_policy(&db, &msg, &queue).await?;
+    let res = queue.enqueue(msg.clone(), url, kind, policy);
+    bot.send_message(msg.chat.id, enqueue_text(&res)).await?;
+
+    Ok(())
+}
+
+// /dl <url> [pick]
+pub async fn cmd_download(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    db: DbPool,
+    queue: DownloadQueue,
+) -> HandlerResult {
+    let mut args = args.split_whitespace();
+    let url = args.next().unwrap_or_default().to_string();
+    if args.next() == Some("pick") {
+        return show_quality_picker(bot, msg, url, db, queue).await;
+    }
+
+    enqueue_download(bot, msg, url, JobKind::Video(None), db, queue).await
+}
+
+pub async fn cmd_audio(
+    bot: Bot,
+    msg: Message,
+    url: String,
+    db: DbPool,
+    queue: DownloadQueue,
+) -> HandlerResult {
+    enqueue_download(bot, msg, url, JobKind::Audio, db, queue).await
+}
+
+pub async fn handle_auto_download(msg: Message, db: DbPool, queue: DownloadQueue) -> HandlerResult {
+    let text = match msg.text().or(msg.caption()) {
+        Some(text) => text,
+        None => return Ok(()),
+    };
+    let url = match extract_url(text).and_then(parse_url) {
+        Some(url) => url,
+        None => return Ok(()),
     };
 
-    if let Err(e) = bot
-        .send_video(msg.chat.id, InputFile::file(&output_path))
-        .await
-    {
-        delete_if_exists(&output_path);
-        return Err(Box::new(e));
+    let link = match find_link(&db, &url).await? {
+        Some(link) => link,
+        None => return Ok(()),
+    };
+    if !link.download_allowed || !link.auto_download {
+        return Ok(());
+    }
+
+    if !can_download(&db, &msg).await? {
+        return Ok(());
+    }
+    if let Some(reason) = quota_denied(&db, &msg, &queue).await? {
+        event!(Level::INFO, "auto download of {} denied: {}", url, reason);
+        return Ok(());
+    }
+
+    event!(Level::INFO, "auto downloading {}", url);
+    let policy = job_policy(&db, &msg, &queue).await?;
+    if let Err(e) = queue.enqueue(msg, url.to_string(), JobKind::Video(None), policy) {
+        event!(Level::WARN, "auto download of {} not queued: {:?}", url, e);
     }
 
     Ok(())
 }
 
-pub async fn cmd_download(bot: Bot, msg: Message, url: String) -> HandlerResult {
-    bot_download(bot, msg, url).await
+pub async fn cmd_cancel(
+    bot: Bot,
+    msg: Message,
+    id: String,
+    db: DbPool,
+    queue: DownloadQueue,
+) -> HandlerResult {
+    let id = if id.trim().is_empty() {
+        None
+    } else {
+        Some(parse_integer!(bot, msg.chat.id, id.trim()))
+    };
+
+    if let Some(user) = msg.from() {
+        let user = find_or_create_user(&db, user).await?;
+        match queue.cancel(id, UserId(user.tg_id as u64), user.is_admin) {
+            Ok(id) => {
+                bot.send_message(msg.chat.id, t!("job_cancelled", id = id.to_string()))
+                    .await?;
+            }
+            Err(CancelError::NotFound) => {
+                bot.send_message(msg.chat.id, t!("job_not_found")).await?;
+            }
+            Err(CancelError::NotPermitted) => {
+                bot.send_message(msg.chat.id, t!("cant_do_that")).await?;
+            }
+        }
+    }
+
+    Ok(())
 }
@@ -0,0 +1,173 @@
+use rust_i18n::t;
+use teloxide::prelude::*;
+use tracing::{event, Level};
+
+use super::queue::DownloadQueue;
+use super::types::HandlerResult;
+use crate::db::chat::{find_or_create_chat, update_chat_format};
+use crate::db::user::find_or_create_user;
+use crate::db::{Chat, DbPool};
+use crate::dl::policy::{parse_list, FormatPolicy};
+use crate::reply_i18n_and_return;
+use crate::util::parse_limit;
+
+pub fn chat_policy(policy: &FormatPolicy, chat: &Chat) -> FormatPolicy {
+    let mut policy = policy.clone();
+    // out of range values would wrap around and lift the cap, so they're skipped
+    if let Some(max_height) = chat.max_height.and_then(|h| u16::try_from(h).ok()) {
+        policy.max_height = max_height;
+    }
+    if let Some(containers) = &chat.containers {
+        policy.containers = parse_list(containers);
+    }
+    if let Some(vcodecs) = &chat.vcodecs {
+        policy.vcodecs = parse_list(vcodecs);
+    }
+    if let Some(acodecs) = &chat.acodecs {
+        policy.acodecs = parse_list(acodecs);
+    }
+    if chat.max_vbr.is_some() {
+        policy.max_vbr = chat.max_vbr;
+    }
+    if chat.max_fps.is_some() {
+        policy.max_fps = chat.max_fps;
+    }
+
+    policy
+}
+
+// private chats have no row in chat table, so they always get global policy
+pub async fn job_policy(
+    db: &DbPool,
+    msg: &Message,
+    queue: &DownloadQueue,
+) -> Result<FormatPolicy, sqlx::Error> {
+    if msg.chat.is_private() {
+        return Ok(queue.policy().clone());
+    }
+
+    let chat = find_or_create_chat(db, &msg.chat).await?;
+    Ok(chat_policy(queue.policy(), &chat))
+}
+
+// sets chat override, "default" value resets it back to global one
+fn set_override(chat: &mut Chat, key: &str, value: &str) -> Option<()> {
+    let list = (value != "default").then(|| value.to_string());
+    match key {
+        "height" => chat.max_height = parse_limit::<u16>(value, false)?.map(i32::from),
+        "containers" => chat.containers = list,
+        "vcodecs" => chat.vcodecs = list,
+        "acodecs" => chat.acodecs = list,
+        "vbr" => chat.max_vbr = parse_limit(value, false)?,
+        "fps" => chat.max_fps = parse_limit(value, false)?,
+        _ => return None,
+    }
+
+    Some(())
+}
+
+pub async fn cmd_format(bot: Bot, msg: Message, db: DbPool, queue: DownloadQueue) -> HandlerResult {
+    let policy = job_policy(&db, &msg, &queue).await?;
+    bot.send_message(
+        msg.chat.id,
+        t!("format_policy", policy = policy.to_string()),
+    )
+    .await?;
+
+    Ok(())
+}
+
+// /setformat <height|containers|vcodecs|acodecs|vbr|fps> <value|default>
+pub async fn cmd_setformat(
+    bot: Bot,
+    msg: Message,
+    text: String,
+    db: DbPool,
+    queue: DownloadQueue,
+) -> HandlerResult {
+    if let Some(user) = msg.from() {
+        let user = find_or_create_user(&db, user).await?;
+        if !user.is_admin {
+            reply_i18n_and_return!(bot, msg.chat.id, "not_an_admin");
+        }
+        if msg.chat.is_private() {
+            reply_i18n_and_return!(bot, msg.chat.id, "setformat_private");
+        }
+
+        let mut chat = find_or_create_chat(&db, &msg.chat).await?;
+        let mut args = text.split_whitespace();
+        let updated = match (args.next(), args.next(), args.next()) {
+            (Some(key), Some(value), None) => set_override(&mut chat, key, value),
+            _ => None,
+        };
+        if updated.is_none() {
+            reply_i18n_and_return!(bot, msg.chat.id, "setformat_usage");
+        }
+
+        update_chat_format(&db, &chat).await?;
+        event!(
+            Level::INFO,
+            "format of {} set to {} by {}",
+            chat,
+            text,
+            user
+        );
+
+        let policy = chat_policy(queue.policy(), &chat);
+        bot.send_message(
+            msg.chat.id,
+            t!("format_policy", policy = policy.to_string()),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chat_policy, set_override};
+    use crate::db::Chat;
+    use crate::dl::policy::FormatPolicy;
+
+    #[test]
+    fn test_chat_policy() {
+        let mut chat = Chat {
+            id: 1,
+            tg_id: -100,
+            username: None,
+            title: "test".to_string(),
+            can_download: true,
+            max_height: None,
+            containers: None,
+            vcodecs: None,
+            acodecs: None,
+            max_vbr: None,
+            max_fps: None,
+            delete_link: false,
+            quota: Default::default(),
+        };
+        let policy = FormatPolicy::default();
+        assert_eq!(chat_policy(&policy, &chat), policy);
+
+        assert!(set_override(&mut chat, "height", "480").is_some());
+        assert!(set_override(&mut chat, "vcodecs", "vp9,avc1").is_some());
+        assert!(set_override(&mut chat, "fps", "high").is_none());
+        assert!(set_override(&mut chat, "bitrate", "1000").is_none());
+        assert!(set_override(&mut chat, "height", "-5").is_none());
+        assert!(set_override(&mut chat, "height", "0").is_none());
+        assert!(set_override(&mut chat, "height", "70000").is_none());
+        assert!(set_override(&mut chat, "vbr", "-1").is_none());
+        let custom = chat_policy(&policy, &chat);
+        assert_eq!(custom.max_height, 480);
+        assert_eq!(custom.vcodecs, vec!["vp9", "avc1"]);
+        assert_eq!(custom.containers, policy.containers);
+
+        assert!(set_override(&mut chat, "height", "default").is_some());
+        assert_eq!(chat_policy(&policy, &chat).max_height, policy.max_height);
+
+        // rows written before the check may still hold negative height
+        chat.max_height = Some(-5);
+        assert_eq!(chat_policy(&policy, &chat).max_height, policy.max_height);
+    }
+}
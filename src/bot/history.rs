@@ -0,0 +1,155 @@
+use rust_i18n::t;
+use teloxide::prelude::*;
+
+use super::quality::format_size;
+use super::types::HandlerResult;
+use crate::db::download::{list_downloads, top_users};
+use crate::db::user::find_or_create_user;
+use crate::db::{DbPool, Download};
+use crate::reply_i18n_and_return;
+
+const HISTORY_LIMIT: i64 = 10;
+const HISTORY_ALL_LIMIT: i64 = 20;
+const TOP_DAYS: i32 = 7;
+const TOP_LIMIT: i64 = 10;
+// errors can hold whole yt-dlp or ffmpeg stderr
+const ERROR_LENGTH: usize = 100;
+// telegram message length limit
+const MESSAGE_LENGTH: usize = 4096;
+
+fn short_error(error: &str) -> String {
+    let line = error.lines().next().unwrap_or("").trim();
+    if line.chars().count() <= ERROR_LENGTH && line.len() == error.trim().len() {
+        return line.to_string();
+    }
+
+    let mut short: String = line.chars().take(ERROR_LENGTH).collect();
+    short.push('…');
+    short
+}
+
+// lines that don't fit in one message are dropped
+fn join_lines(header: &str, lines: &[String]) -> String {
+    let mut text = header.to_string();
+    let mut length = text.chars().count();
+    for (i, line) in lines.iter().enumerate() {
+        let separator = if i == 0 { 0 } else { 1 };
+        let line_length = line.chars().count() + separator;
+        if length + line_length > MESSAGE_LENGTH {
+            break;
+        }
+        if separator > 0 {
+            text.push('\n');
+        }
+        text.push_str(line);
+        length += line_length;
+    }
+
+    text
+}
+
+fn history_line(download: &Download, with_user: bool) -> String {
+    let mut line = format!(
+        "{} {} {}",
+        download.created_at.format("%Y-%m-%d %H:%M"),
+        download.status,
+        download.url
+    );
+    if let Some(bytes) = download.bytes {
+        line.push_str(&format!(" {}", format_size(bytes as u64)));
+    }
+    line.push_str(&format!(" {:.1}s", download.elapsed_ms as f64 / 1000.0));
+    if with_user {
+        let user_name = download.user_name.as_deref().unwrap_or("-");
+        line.push_str(&format!(" by {}", user_name));
+    }
+    if let Some(error) = &download.error {
+        line.push_str(&format!("\n  {}", short_error(error)));
+    }
+
+    line
+}
+
+// /history - own downloads, /history all and /history top are for admins
+pub async fn cmd_history(bot: Bot, msg: Message, text: String, db: DbPool) -> HandlerResult {
+    if let Some(user) = msg.from() {
+        let user = find_or_create_user(&db, user).await?;
+        let mode = text.trim();
+        if !mode.is_empty() && !user.is_admin {
+            reply_i18n_and_return!(bot, msg.chat.id, "not_an_admin");
+        }
+
+        let text = match mode {
+            "" | "all" => {
+                let (user_id, limit) = if mode.is_empty() {
+                    (Some(user.id), HISTORY_LIMIT)
+                } else {
+                    (None, HISTORY_ALL_LIMIT)
+                };
+                let downloads = list_downloads(&db, user_id, limit).await?;
+                if downloads.is_empty() {
+                    reply_i18n_and_return!(bot, msg.chat.id, "history_empty");
+                }
+
+                let lines: Vec<String> = downloads
+                    .iter()
+                    .map(|download| history_line(download, user_id.is_none()))
+                    .collect();
+                join_lines(&t!("history_header"), &lines)
+            }
+            "top" => {
+                let usages = top_users(&db, TOP_DAYS, TOP_LIMIT).await?;
+                let lines: Vec<String> = usages
+                    .iter()
+                    .map(|usage| {
+                        format!(
+                            "{} - {} downloads, {}",
+                            usage.user_name,
+                            usage.downloads,
+                            format_size(usage.bytes as u64)
+                        )
+                    })
+                    .collect();
+                format!(
+                    "{}{}",
+                    t!("history_top_header", days = TOP_DAYS.to_string()),
+                    lines.join("\n")
+                )
+            }
+            _ => {
+                reply_i18n_and_return!(bot, msg.chat.id, "history_usage");
+            }
+        };
+
+        bot.send_message(msg.chat.id, text).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{join_lines, short_error, MESSAGE_LENGTH};
+
+    #[test]
+    fn test_short_error() {
+        assert_eq!(short_error("video is too long"), "video is too long");
+        assert_eq!(
+            short_error("ERROR: Unsupported URL\nTraceback:\n  ..."),
+            "ERROR: Unsupported URL…"
+        );
+        let long = "x".repeat(500);
+        assert_eq!(short_error(&long).chars().count(), 101);
+    }
+
+    #[test]
+    fn test_join_lines() {
+        let lines = vec!["a".to_string(), "b".to_string()];
+        assert_eq!(join_lines("History:\n", &lines), "History:\na\nb");
+
+        let lines = vec!["x".repeat(1000); 20];
+        let text = join_lines("History:\n", &lines);
+        assert!(text.chars().count() <= MESSAGE_LENGTH);
+        assert_eq!(text.matches('\n').count(), 4);
+    }
+}
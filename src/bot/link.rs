@@ -0,0 +1,222 @@
+use rust_i18n::t;
+use teloxide::prelude::*;
+use tracing::{event, Level};
+
+use super::types::HandlerResult;
+use crate::db::link::{
+    create_link, delete_link, list_links, set_link_auto_download, set_link_download_allowed,
+};
+use crate::db::user::find_or_create_user;
+use crate::db::DbPool;
+use crate::{parse_integer, reply_i18n_and_return};
+
+pub fn parse_switch(value: &str) -> Option<bool> {
+    match value {
+        "on" | "true" | "1" => Some(true),
+        "off" | "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+// links are matched against lowercased host, so store domain the same way.
+// scheme and trailing slash are dropped in case a whole url was pasted
+fn normalize_domain(text: &str) -> Option<String> {
+    let domain = text.to_lowercase();
+    let domain = domain
+        .strip_prefix("https://")
+        .or_else(|| domain.strip_prefix("http://"))
+        .unwrap_or(&domain)
+        .trim_end_matches('/')
+        .trim_end_matches('.');
+    if !domain.contains('.') || domain.contains('/') || domain.starts_with('.') {
+        return None;
+    }
+
+    Some(domain.to_string())
+}
+
+// /addlink <domain> [path] [auto]
+fn parse_addlink(text: &str) -> Option<(String, Option<&str>, bool)> {
+    let mut args = text.split_whitespace();
+    let domain = normalize_domain(args.next()?)?;
+
+    let mut path = None;
+    let mut auto = false;
+    for arg in args {
+        if arg == "auto" && !auto {
+            auto = true;
+        } else if arg.starts_with('/') && path.is_none() {
+            path = Some(arg);
+        } else {
+            return None;
+        }
+    }
+
+    Some((domain, path, auto))
+}
+
+pub async fn cmd_addlink(bot: Bot, msg: Message, text: String, db: DbPool) -> HandlerResult {
+    if let Some(user) = msg.from() {
+        let user = find_or_create_user(&db, user).await?;
+        if !user.is_admin {
+            reply_i18n_and_return!(bot, msg.chat.id, "not_an_admin");
+        }
+
+        let (domain, path, auto) = match parse_addlink(&text) {
+            Some(args) => args,
+            None => {
+                reply_i18n_and_return!(bot, msg.chat.id, "addlink_usage");
+            }
+        };
+
+        let link = create_link(&db, &domain, path, auto).await?;
+        event!(Level::INFO, "added link {} by {}", link, user);
+        bot.send_message(msg.chat.id, t!("link_added", link = link.to_string()))
+            .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn cmd_rmlink(bot: Bot, msg: Message, id: String, db: DbPool) -> HandlerResult {
+    let id: i32 = parse_integer!(bot, msg.chat.id, id);
+
+    if let Some(user) = msg.from() {
+        let user = find_or_create_user(&db, user).await?;
+        if !user.is_admin {
+            reply_i18n_and_return!(bot, msg.chat.id, "not_an_admin");
+        }
+
+        if !delete_link(&db, id).await? {
+            reply_i18n_and_return!(bot, msg.chat.id, "link_not_found");
+        }
+
+        event!(Level::INFO, "removed link {} by {}", id, user);
+        bot.send_message(msg.chat.id, t!("link_removed")).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn cmd_listlinks(bot: Bot, msg: Message, db: DbPool) -> HandlerResult {
+    if let Some(user) = msg.from() {
+        let user = find_or_create_user(&db, user).await?;
+        if !user.is_admin {
+            reply_i18n_and_return!(bot, msg.chat.id, "not_an_admin");
+        }
+
+        let links = list_links(&db).await?;
+
+        let mut list = String::new();
+        list.push_str(t!("link_list_header").to_string().as_str());
+        for link in links {
+            list.push_str(format!("{}\n", link).as_str());
+        }
+        bot.send_message(msg.chat.id, list).await?;
+    }
+
+    Ok(())
+}
+
+// /setlink <id> auto|download on|off
+pub async fn cmd_setlink(bot: Bot, msg: Message, text: String, db: DbPool) -> HandlerResult {
+    if let Some(user) = msg.from() {
+        let user = find_or_create_user(&db, user).await?;
+        if !user.is_admin {
+            reply_i18n_and_return!(bot, msg.chat.id, "not_an_admin");
+        }
+
+        let args: Vec<&str> = text.split_whitespace().collect();
+        if args.len() != 3 {
+            reply_i18n_and_return!(bot, msg.chat.id, "setlink_usage");
+        }
+
+        let id: i32 = parse_integer!(bot, msg.chat.id, args[0]);
+        let value = match parse_switch(args[2]) {
+            Some(value) => value,
+            None => {
+                reply_i18n_and_return!(bot, msg.chat.id, "setlink_usage");
+            }
+        };
+
+        let updated = match args[1] {
+            "auto" => set_link_auto_download(&db, id, value).await?,
+            "download" => set_link_download_allowed(&db, id, value).await?,
+            _ => {
+                reply_i18n_and_return!(bot, msg.chat.id, "setlink_usage");
+            }
+        };
+        if !updated {
+            reply_i18n_and_return!(bot, msg.chat.id, "link_not_found");
+        }
+
+        event!(
+            Level::INFO,
+            "set link {} {} to {} by {}",
+            id,
+            args[1],
+            value,
+            user
+        );
+        bot.send_message(msg.chat.id, t!("link_updated")).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_domain, parse_addlink, parse_switch};
+
+    #[test]
+    fn test_parse_switch() {
+        assert_eq!(parse_switch("on"), Some(true));
+        assert_eq!(parse_switch("1"), Some(true));
+        assert_eq!(parse_switch("off"), Some(false));
+        assert_eq!(parse_switch("false"), Some(false));
+        assert_eq!(parse_switch(""), None);
+        assert_eq!(parse_switch("yes"), None);
+    }
+
+    #[test]
+    fn test_normalize_domain() {
+        let domain = |d: &str| Some(d.to_string());
+        assert_eq!(normalize_domain("YouTube.com"), domain("youtube.com"));
+        assert_eq!(
+            normalize_domain("https://youtube.com/"),
+            domain("youtube.com")
+        );
+        assert_eq!(
+            normalize_domain("http://m.youtube.com"),
+            domain("m.youtube.com")
+        );
+        assert_eq!(normalize_domain("youtu.be."), domain("youtu.be"));
+        assert_eq!(normalize_domain("localhost"), None);
+        assert_eq!(normalize_domain("youtube.com/shorts"), None);
+        assert_eq!(normalize_domain(".com"), None);
+    }
+
+    #[test]
+    fn test_parse_addlink() {
+        assert_eq!(
+            parse_addlink("tiktok.com"),
+            Some(("tiktok.com".to_string(), None, false))
+        );
+        assert_eq!(
+            parse_addlink("youtube.com /shorts auto"),
+            Some(("youtube.com".to_string(), Some("/shorts"), true))
+        );
+        assert_eq!(
+            parse_addlink("instagram.com auto /reel"),
+            Some(("instagram.com".to_string(), Some("/reel"), true))
+        );
+        assert_eq!(
+            parse_addlink("https://YouTube.com/ /shorts"),
+            Some(("youtube.com".to_string(), Some("/shorts"), false))
+        );
+        assert_eq!(parse_addlink("youtube.com/shorts"), None);
+        assert_eq!(parse_addlink(""), None);
+        assert_eq!(parse_addlink("reddit.com auto auto"), None);
+        assert_eq!(parse_addlink("reddit.com whatever"), None);
+    }
+}
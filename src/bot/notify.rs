@@ -1,11 +1,91 @@
+use rust_i18n::t;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, MessageId};
 use teloxide::{prelude::*, types::Recipient};
 use tracing::{event, Level};
 
+use crate::db::notification::{create_notification, take_notifications};
+use crate::db::user::find_or_create_user;
 use crate::db::{DbPool, User};
 
+use super::request::{approve_request, decline_request};
+use super::request_chat::{approve_chat_request, decline_chat_request};
 use super::types::HandlerResult;
 
-pub async fn notify_admins(bot: &Bot, db: &DbPool, message: String) -> HandlerResult {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RequestKind {
+    User,
+    Chat,
+}
+
+impl RequestKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::User => "request",
+            Self::Chat => "request_chat",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decision {
+    Approve,
+    Decline,
+}
+
+// callback data is limited to 64 bytes, so keep it short: r:u:a:12
+fn request_data(kind: RequestKind, decision: Decision, id: i32) -> String {
+    let kind = match kind {
+        RequestKind::User => 'u',
+        RequestKind::Chat => 'c',
+    };
+    let decision = match decision {
+        Decision::Approve => 'a',
+        Decision::Decline => 'd',
+    };
+    format!("r:{}:{}:{}", kind, decision, id)
+}
+
+pub fn parse_request_data(data: &str) -> Option<(RequestKind, Decision, i32)> {
+    let mut parts = data.strip_prefix("r:")?.split(':');
+    let kind = match parts.next()? {
+        "u" => RequestKind::User,
+        "c" => RequestKind::Chat,
+        _ => return None,
+    };
+    let decision = match parts.next()? {
+        "a" => Decision::Approve,
+        "d" => Decision::Decline,
+        _ => return None,
+    };
+    let id = parts.next()?.parse().ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+
+    Some((kind, decision, id))
+}
+
+fn request_keyboard(kind: RequestKind, id: i32) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new([[
+        InlineKeyboardButton::callback(
+            t!("button_approve"),
+            request_data(kind, Decision::Approve, id),
+        ),
+        InlineKeyboardButton::callback(
+            t!("button_decline"),
+            request_data(kind, Decision::Decline, id),
+        ),
+    ]])
+}
+
+// sends request to every admin with approve/decline buttons
+pub async fn notify_admins(
+    bot: &Bot,
+    db: &DbPool,
+    message: String,
+    kind: RequestKind,
+    request_id: i32,
+) -> HandlerResult {
     let admins: Vec<User> = sqlx::query_as(
         r#"SELECT * FROM "user" WHERE is_admin = true AND has_private_chat = true;"#,
     )
@@ -15,10 +95,108 @@ pub async fn notify_admins(bot: &Bot, db: &DbPool, message: String) -> HandlerRe
     for admin in admins {
         let res = bot
             .send_message(Recipient::Id(ChatId(admin.tg_id)), &message)
+            .reply_markup(request_keyboard(kind, request_id))
+            .await;
+        let sent = match res {
+            Ok(sent) => sent,
+            Err(e) => {
+                event!(Level::WARN, "notify admin {} error {}", admin, e);
+                continue;
+            }
+        };
+        create_notification(db, kind.as_str(), request_id, admin.tg_id, sent.id.0).await?;
+    }
+    Ok(())
+}
+
+// tells requester about the decision. they may have blocked the bot or the chat
+// may be gone, which shouldn't stop admin notifications from being resolved
+pub async fn notify_requester(bot: &Bot, chat_id: ChatId, text: String) {
+    if let Err(e) = bot.send_message(Recipient::Id(chat_id), text).await {
+        event!(Level::WARN, "notify requester {} error {}", chat_id.0, e);
+    }
+}
+
+// replaces request notifications of all admins with the decision, removing buttons
+pub async fn resolve_notifications(
+    bot: &Bot,
+    db: &DbPool,
+    kind: RequestKind,
+    request_id: i32,
+    text: &str,
+) -> HandlerResult {
+    for notification in take_notifications(db, kind.as_str(), request_id).await? {
+        let res = bot
+            .edit_message_text(
+                ChatId(notification.chat_tg_id),
+                MessageId(notification.message_id),
+                text,
+            )
             .await;
         if let Err(e) = res {
-            event!(Level::WARN, "notify admin {} error {}", admin, e);
+            event!(
+                Level::WARN,
+                "update notification {} error {}",
+                notification.id,
+                e
+            );
         }
     }
     Ok(())
 }
+
+pub async fn handle_request_callback(
+    bot: Bot,
+    q: CallbackQuery,
+    kind: RequestKind,
+    decision: Decision,
+    id: i32,
+    db: DbPool,
+) -> HandlerResult {
+    let admin = find_or_create_user(&db, &q.from).await?;
+    if !admin.is_admin {
+        bot.answer_callback_query(q.id)
+            .text(t!("not_an_admin"))
+            .await?;
+        return Ok(());
+    }
+
+    let reply = match (kind, decision) {
+        (RequestKind::User, Decision::Approve) => approve_request(&bot, &db, &admin, id).await?,
+        (RequestKind::User, Decision::Decline) => {
+            decline_request(&bot, &db, &admin, id, None).await?
+        }
+        (RequestKind::Chat, Decision::Approve) => {
+            approve_chat_request(&bot, &db, &admin, id).await?
+        }
+        (RequestKind::Chat, Decision::Decline) => {
+            decline_chat_request(&bot, &db, &admin, id, None).await?
+        }
+    };
+    bot.answer_callback_query(q.id).text(t!(reply)).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_request_data, request_data, Decision, RequestKind};
+
+    #[test]
+    fn test_request_data() {
+        let data = request_data(RequestKind::Chat, Decision::Decline, 42);
+        assert_eq!(data, "r:c:d:42");
+        assert_eq!(
+            parse_request_data(&data),
+            Some((RequestKind::Chat, Decision::Decline, 42))
+        );
+        assert_eq!(
+            parse_request_data("r:u:a:7"),
+            Some((RequestKind::User, Decision::Approve, 7))
+        );
+        assert_eq!(parse_request_data("q:1080:mp4"), None);
+        assert_eq!(parse_request_data("r:x:a:7"), None);
+        assert_eq!(parse_request_data("r:u:a:seven"), None);
+        assert_eq!(parse_request_data("r:u:a:7:8"), None);
+    }
+}
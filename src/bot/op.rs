@@ -4,22 +4,21 @@ use teloxide::prelude::*;
 use tracing::{event, Level};
 
 use super::types::HandlerResult;
-use crate::db::user::find_or_create_user;
+use super::users::admin_and_target;
+use crate::db::user::{find_or_create_user, set_user_admin};
 use crate::db::DbPool;
+use crate::reply_i18n_and_return;
 
-pub async fn cmd_op(bot: Bot, msg: Message, db: DbPool) -> HandlerResult {
+pub async fn cmd_op(bot: Bot, msg: Message, text: String, db: DbPool) -> HandlerResult {
     let admins: i64 = sqlx::query(r#"SELECT COUNT(*) FROM "user" WHERE is_admin = true"#)
         .fetch_one(&db)
         .await?
         .get(0);
 
-    if let Some(tg_user) = msg.from() {
-        if admins == 0 {
+    if admins == 0 {
+        if let Some(tg_user) = msg.from() {
             let user = find_or_create_user(&db, tg_user).await?;
-            sqlx::query(r#"UPDATE "user" SET is_admin = true WHERE id = $1;"#)
-                .bind(user.id)
-                .execute(&db)
-                .await?;
+            set_user_admin(&db, user.id, true).await?;
 
             event!(
                 Level::INFO,
@@ -28,25 +27,30 @@ pub async fn cmd_op(bot: Bot, msg: Message, db: DbPool) -> HandlerResult {
                 user.username_or_name()
             );
             bot.send_message(msg.chat.id, t!("op_yourself")).await?;
-        } else {
-            let user = find_or_create_user(&db, tg_user).await?;
-            if user.is_admin {
-                if let Some(target) = msg.reply_to_message().and_then(|m| m.from()) {
-                    let target = find_or_create_user(&db, target).await?;
-                    sqlx::query(r#"UPDATE "user" SET is_admin = 1 WHERE id = $1;"#)
-                        .bind(target.id)
-                        .execute(&db)
-                        .await?;
-
-                    event!(Level::INFO, "opped {}", target);
-                    bot.send_message(msg.chat.id, "opped").await?;
-                } else {
-                    bot.send_message(msg.chat.id, t!("has_to_reply")).await?;
-                }
-            } else {
-                bot.send_message(msg.chat.id, t!("cant_do_that")).await?;
-            }
         }
+    } else if let Some((user, target)) = admin_and_target(&bot, &msg, &text, &db).await? {
+        set_user_admin(&db, target.id, true).await?;
+
+        event!(Level::INFO, "opped {} by {}", target, user);
+        bot.send_message(msg.chat.id, t!("user_opped", user = target.to_string()))
+            .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn cmd_deop(bot: Bot, msg: Message, text: String, db: DbPool) -> HandlerResult {
+    if let Some((user, target)) = admin_and_target(&bot, &msg, &text, &db).await? {
+        // so there is always at least one admin left
+        if target.id == user.id {
+            reply_i18n_and_return!(bot, msg.chat.id, "cant_do_that");
+        }
+
+        set_user_admin(&db, target.id, false).await?;
+
+        event!(Level::INFO, "deopped {} by {}", target, user);
+        bot.send_message(msg.chat.id, t!("user_deopped", user = target.to_string()))
+            .await?;
     }
 
     Ok(())
@@ -0,0 +1,322 @@
+use rust_i18n::t;
+use std::sync::{Arc, Mutex};
+use teloxide::prelude::*;
+use teloxide::types::MessageId;
+use teloxide::RequestError;
+use tracing::{event, Level};
+
+use super::dl::{download_denied, download_error_text, enqueue_text};
+use super::format::job_policy;
+use super::queue::{DownloadQueue, JobKind};
+use super::quota::quota_remaining;
+use super::sanitize::parse_url;
+use super::types::HandlerResult;
+use crate::db::download::DownloadStatus;
+use crate::db::user::find_or_create_user;
+use crate::db::DbPool;
+use crate::reply_i18n_and_return;
+
+// 1-based and inclusive, open end means until the end of playlist
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlaylistRange {
+    pub start: usize,
+    pub end: Option<usize>,
+}
+
+impl Default for PlaylistRange {
+    fn default() -> Self {
+        Self {
+            start: 1,
+            end: None,
+        }
+    }
+}
+
+impl PlaylistRange {
+    // 5, 3-10 or 3-
+    fn parse(text: &str) -> Option<Self> {
+        let (start, end) = match text.split_once('-') {
+            Some((start, "")) => (start.parse().ok()?, None),
+            Some((start, end)) => (start.parse().ok()?, Some(end.parse().ok()?)),
+            None => {
+                let item = text.parse().ok()?;
+                (item, Some(item))
+            }
+        };
+        if start == 0 || end.is_some_and(|end| end < start) {
+            return None;
+        }
+
+        Some(Self { start, end })
+    }
+
+    // one item over the limit is loaded, so too long playlist can be told apart
+    fn load_end(&self, max_items: Option<usize>) -> Option<usize> {
+        match (self.end, max_items) {
+            (Some(end), Some(max)) => Some(end.min(self.start + max)),
+            (None, Some(max)) => Some(self.start + max),
+            (end, None) => end,
+        }
+    }
+}
+
+// /playlist <url> [range] [audio]
+fn parse_args(text: &str) -> Option<(&str, PlaylistRange, JobKind)> {
+    let mut args = text.split_whitespace();
+    let url = args.next()?;
+    let mut range = None;
+    let mut kind = None;
+    for arg in args {
+        match arg {
+            "audio" if kind.is_none() => kind = Some(JobKind::Audio),
+            _ if range.is_none() => range = Some(PlaylistRange::parse(arg)?),
+            _ => return None,
+        }
+    }
+
+    Some((
+        url,
+        range.unwrap_or_default(),
+        kind.unwrap_or(JobKind::Video(None)),
+    ))
+}
+
+// shared by all jobs of one /playlist, queue runs them one at a time and in order
+pub struct Playlist {
+    title: String,
+    chat_id: ChatId,
+    message_id: MessageId,
+    results: Mutex<Vec<Option<DownloadStatus>>>,
+}
+
+#[derive(Clone)]
+pub struct PlaylistItem {
+    pub playlist: Arc<Playlist>,
+    pub index: usize,
+}
+
+impl Playlist {
+    pub fn new(title: String, chat_id: ChatId, message_id: MessageId, total: usize) -> Self {
+        Self {
+            title,
+            chat_id,
+            message_id,
+            results: Mutex::new(vec![None; total]),
+        }
+    }
+
+    // only the first result of an item counts, returns true once all of them are in
+    fn finish(&self, index: usize, status: DownloadStatus) -> bool {
+        let mut results = self.results.lock().unwrap();
+        if results[index].is_some() {
+            return false;
+        }
+        results[index] = Some(status);
+
+        results.iter().all(Option::is_some)
+    }
+
+    fn summary_text(&self) -> String {
+        let results = self.results.lock().unwrap();
+        let count = |wanted: &[DownloadStatus]| {
+            results
+                .iter()
+                .filter(|status| matches!(status, Some(status) if wanted.contains(status)))
+                .count()
+                .to_string()
+        };
+        t!(
+            "playlist_summary",
+            title = self.title,
+            done = count(&[DownloadStatus::Done, DownloadStatus::Cached]),
+            failed = count(&[DownloadStatus::Failed]),
+            cancelled = count(&[DownloadStatus::Cancelled]),
+            total = results.len().to_string()
+        )
+        .to_string()
+    }
+}
+
+// records result of the item, the last one to finish posts the summary
+pub async fn finish_item(
+    bot: &Bot,
+    item: &PlaylistItem,
+    status: DownloadStatus,
+) -> Result<(), RequestError> {
+    let playlist = &item.playlist;
+    if !playlist.finish(item.index, status) {
+        return Ok(());
+    }
+
+    bot.send_message(playlist.chat_id, playlist.summary_text())
+        .reply_to_message_id(playlist.message_id)
+        .allow_sending_without_reply(true)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn cmd_playlist(
+    bot: Bot,
+    msg: Message,
+    text: String,
+    db: DbPool,
+    queue: DownloadQueue,
+) -> HandlerResult {
+    let (url, range, kind) = match parse_args(&text) {
+        Some(args) => args,
+        None => {
+            reply_i18n_and_return!(bot, msg.chat.id, "playlist_usage");
+        }
+    };
+    if let Some(reason) = download_denied(&db, &msg, url, &queue).await? {
+        reply_i18n_and_return!(bot, msg.chat.id, reason);
+    }
+
+    let is_admin = match msg.from() {
+        Some(user) => find_or_create_user(&db, user).await?.is_admin,
+        None => false,
+    };
+    let limits = queue.limits();
+    let max_items = (!is_admin).then_some(limits.max_playlist_items);
+    // loading playlist goes through the queue like any other info fetch
+    let job = match queue.start_info(&msg) {
+        Ok(job) => job,
+        Err(e) => {
+            bot.send_message(msg.chat.id, enqueue_text(&Err(e))).await?;
+            return Ok(());
+        }
+    };
+    let res = job
+        .load_playlist(url, range.start, range.load_end(max_items))
+        .await;
+    // info job counts against limits, so it has to be gone before items are queued
+    drop(job);
+    let playlist = match res {
+        Ok(playlist) => playlist,
+        Err(e) => {
+            bot.send_message(msg.chat.id, download_error_text(&e))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let mut urls: Vec<String> = playlist
+        .entries
+        .into_iter()
+        .filter_map(|entry| entry.url)
+        .filter(|url| parse_url(url).is_some())
+        .collect();
+    if urls.is_empty() {
+        reply_i18n_and_return!(bot, msg.chat.id, "playlist_empty");
+    }
+    if let Some(max_items) = max_items {
+        if urls.len() > max_items {
+            bot.send_message(
+                msg.chat.id,
+                t!("playlist_too_long", max = max_items.to_string()),
+            )
+            .await?;
+            return Ok(());
+        }
+    }
+
+    // every item counts against quota, so only as many as still fit are queued
+    let mut trimmed = false;
+    if let Some((remaining, exceeded_key)) = quota_remaining(&db, &msg, &queue).await? {
+        if remaining == 0 {
+            reply_i18n_and_return!(bot, msg.chat.id, exceeded_key);
+        }
+        if urls.len() > remaining as usize {
+            urls.truncate(remaining as usize);
+            trimmed = true;
+        }
+    }
+
+    let title = playlist.title.unwrap_or(playlist.id);
+    let count = urls.len();
+    let shared = Playlist::new(title.clone(), msg.chat.id, msg.id, count);
+    let policy = job_policy(&db, &msg, &queue).await?;
+    let text = match queue.enqueue_playlist(msg.clone(), urls, kind, policy, shared) {
+        Ok(ids) => {
+            event!(Level::INFO, "queued playlist {} of {} items", title, count);
+            let mut text = t!(
+                "playlist_queued",
+                title = title,
+                count = count.to_string(),
+                first = ids[0].to_string(),
+                last = ids[count - 1].to_string()
+            )
+            .to_string();
+            if trimmed {
+                text.push('\n');
+                text.push_str(&t!("playlist_quota_trimmed", count = count.to_string()));
+            }
+            text
+        }
+        Err(e) => enqueue_text(&Err(e)),
+    };
+    bot.send_message(msg.chat.id, text).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use teloxide::types::{ChatId, MessageId};
+
+    use super::{parse_args, Playlist, PlaylistRange};
+    use crate::bot::queue::JobKind;
+    use crate::db::download::DownloadStatus;
+
+    #[test]
+    fn test_playlist_range() {
+        let range = |start, end| Some(PlaylistRange { start, end });
+        assert_eq!(PlaylistRange::parse("5"), range(5, Some(5)));
+        assert_eq!(PlaylistRange::parse("3-10"), range(3, Some(10)));
+        assert_eq!(PlaylistRange::parse("3-"), range(3, None));
+        assert_eq!(PlaylistRange::parse("0-3"), None);
+        assert_eq!(PlaylistRange::parse("10-3"), None);
+        assert_eq!(PlaylistRange::parse("-3"), None);
+        assert_eq!(PlaylistRange::parse("a-b"), None);
+    }
+
+    #[test]
+    fn test_load_end() {
+        let range = PlaylistRange {
+            start: 3,
+            end: Some(10),
+        };
+        assert_eq!(range.load_end(None), Some(10));
+        assert_eq!(range.load_end(Some(5)), Some(8));
+        assert_eq!(range.load_end(Some(25)), Some(10));
+        assert_eq!(PlaylistRange::default().load_end(Some(25)), Some(26));
+        assert_eq!(PlaylistRange::default().load_end(None), None);
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let url = "https://www.youtube.com/playlist?list=PL1";
+        let (_, range, kind) = parse_args(url).unwrap();
+        assert_eq!(range, PlaylistRange::default());
+        assert_eq!(kind, JobKind::Video(None));
+
+        let (_, range, kind) = parse_args(&format!("{} 2-4 audio", url)).unwrap();
+        assert_eq!(range.start, 2);
+        assert_eq!(kind, JobKind::Audio);
+
+        assert!(parse_args("").is_none());
+        assert!(parse_args(&format!("{} 2-4 5", url)).is_none());
+        assert!(parse_args(&format!("{} foo", url)).is_none());
+    }
+
+    #[test]
+    fn test_playlist_finish() {
+        let playlist = Playlist::new("test".to_string(), ChatId(1), MessageId(1), 3);
+        assert!(!playlist.finish(1, DownloadStatus::Done));
+        assert!(!playlist.finish(0, DownloadStatus::Failed));
+        // only the first result counts
+        assert!(!playlist.finish(0, DownloadStatus::Done));
+        assert!(playlist.finish(2, DownloadStatus::Cancelled));
+    }
+}
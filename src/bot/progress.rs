@@ -0,0 +1,56 @@
+use rust_i18n::t;
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::MessageId;
+use tracing::{event, Level};
+
+use crate::dl::progress::{Progress, ProgressReceiver};
+
+// Telegram rate limits message edits, so don't update status too often
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(3);
+
+pub fn progress_text(progress: Progress) -> String {
+    match progress {
+        Progress::FetchingInfo => t!("progress_fetching_info").to_string(),
+        Progress::Item(item, total) => t!(
+            "progress_item",
+            item = item.to_string(),
+            total = total.to_string()
+        )
+        .to_string(),
+        Progress::Video(percent) => {
+            t!("progress_video", percent = format!("{:.0}", percent)).to_string()
+        }
+        Progress::Audio(percent) => {
+            t!("progress_audio", percent = format!("{:.0}", percent)).to_string()
+        }
+        Progress::Merging => t!("progress_merging").to_string(),
+        Progress::Converting => t!("progress_converting").to_string(),
+        Progress::Encoding => t!("progress_encoding").to_string(),
+        Progress::Splitting => t!("progress_splitting").to_string(),
+        Progress::Uploading => t!("progress_uploading").to_string(),
+    }
+}
+
+// edits status message until progress sender is dropped or task is aborted
+pub async fn report_progress(
+    bot: Bot,
+    chat_id: ChatId,
+    message_id: MessageId,
+    mut rx: ProgressReceiver,
+) {
+    let mut last_text = progress_text(*rx.borrow());
+    while rx.changed().await.is_ok() {
+        let text = progress_text(*rx.borrow_and_update());
+        if text == last_text {
+            continue;
+        }
+
+        if let Err(e) = bot.edit_message_text(chat_id, message_id, &text).await {
+            event!(Level::WARN, "progress edit error {}", e);
+        }
+        last_text = text;
+
+        tokio::time::sleep(PROGRESS_INTERVAL).await;
+    }
+}
@@ -0,0 +1,199 @@
+use rust_i18n::t;
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+use super::dl::{download_denied, download_error_text, enqueue_text};
+use super::format::job_policy;
+use super::queue::{DownloadQueue, JobKind};
+use super::sanitize::extract_url;
+use super::types::HandlerResult;
+use crate::db::user::find_or_create_user;
+use crate::db::DbPool;
+use crate::dl::policy::FormatPolicy;
+use crate::dl::yt_dlp::{VideoQuality, YtDlpInfo};
+use crate::reply_i18n_and_return;
+
+// too many buttons are hard to pick from
+const MAX_QUALITIES: usize = 12;
+
+pub fn format_size(bytes: u64) -> String {
+    const MB: f64 = 1024.0 * 1024.0;
+    let mb = bytes as f64 / MB;
+    if mb >= 1024.0 {
+        format!("{:.1} GB", mb / 1024.0)
+    } else {
+        format!("{:.1} MB", mb)
+    }
+}
+
+// callback data is limited to 64 bytes, so keep it short: q:1080:mp4
+fn quality_data(quality: &VideoQuality) -> String {
+    format!("q:{}:{}", quality.height, quality.ext)
+}
+
+pub fn parse_quality_data(data: &str) -> Option<VideoQuality> {
+    let mut parts = data.splitn(3, ':');
+    if parts.next()? != "q" {
+        return None;
+    }
+
+    let height = parts.next()?.parse().ok()?;
+    let ext = parts.next()?;
+    if ext.is_empty() {
+        return None;
+    }
+
+    Some(VideoQuality {
+        height,
+        ext: ext.to_string(),
+    })
+}
+
+fn quality_keyboard(info: &YtDlpInfo, policy: &FormatPolicy) -> Option<InlineKeyboardMarkup> {
+    let audio_size = info
+        .best_audio_format(policy)
+        .and_then(|af| af.approx_filesize())
+        .unwrap_or(0);
+    let rows: Vec<Vec<InlineKeyboardButton>> = info
+        .video_qualities()
+        .into_iter()
+        .take(MAX_QUALITIES)
+        .map(|(quality, vf)| {
+            let mut label = format!("{}p {}", quality.height, quality.ext);
+            if let Some(size) = vf.approx_filesize() {
+                label.push_str(&format!(" ~{}", format_size(size + audio_size)));
+            }
+
+            vec![InlineKeyboardButton::callback(
+                label,
+                quality_data(&quality),
+            )]
+        })
+        .collect();
+
+    if rows.is_empty() {
+        None
+    } else {
+        Some(InlineKeyboardMarkup::new(rows))
+    }
+}
+
+pub async fn show_quality_picker(
+    bot: Bot,
+    msg: Message,
+    url: String,
+    db: DbPool,
+    queue: DownloadQueue,
+) -> HandlerResult {
+    if let Some(reason) = download_denied(&db, &msg, &url, &queue).await? {
+        reply_i18n_and_return!(bot, msg.chat.id, reason);
+    }
+
+    // fetching info is as heavy as a download start, so it goes through the queue
+    let job = match queue.start_info(&msg) {
+        Ok(job) => job,
+        Err(e) => {
+            bot.send_message(msg.chat.id, enqueue_text(&Err(e))).await?;
+            return Ok(());
+        }
+    };
+    let info = match job.load_info(&url).await {
+        Ok(info) => info,
+        Err(e) => {
+            bot.send_message(msg.chat.id, download_error_text(&e))
+                .await?;
+            return Ok(());
+        }
+    };
+    drop(job);
+
+    let policy = job_policy(&db, &msg, &queue).await?;
+    let keyboard = match quality_keyboard(&info, &policy) {
+        Some(keyboard) => keyboard,
+        None => {
+            reply_i18n_and_return!(bot, msg.chat.id, "quality_no_formats");
+        }
+    };
+    // picker replies to the command, callback takes url and requester from there
+    bot.send_message(msg.chat.id, t!("quality_pick", title = info.title))
+        .reply_to_message_id(msg.id)
+        .reply_markup(keyboard)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn handle_quality_callback(
+    bot: Bot,
+    q: CallbackQuery,
+    quality: VideoQuality,
+    db: DbPool,
+    queue: DownloadQueue,
+) -> HandlerResult {
+    let picker = match &q.message {
+        Some(picker) => picker,
+        None => return Ok(()),
+    };
+    let (cmd, url) = match picker
+        .reply_to_message()
+        .and_then(|cmd| Some((cmd, extract_url(cmd.text()?)?)))
+    {
+        Some(found) => found,
+        None => {
+            bot.answer_callback_query(q.id.clone())
+                .text(t!("quality_expired"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let user = find_or_create_user(&db, &q.from).await?;
+    let requester = cmd.from().map(|user| user.id);
+    if requester != Some(q.from.id) && !user.is_admin {
+        bot.answer_callback_query(q.id.clone())
+            .text(t!("cant_do_that"))
+            .await?;
+        return Ok(());
+    }
+    bot.answer_callback_query(q.id.clone()).await?;
+
+    let text = match download_denied(&db, cmd, url, &queue).await? {
+        Some(reason) => t!(reason).to_string(),
+        None => {
+            let kind = JobKind::Video(Some(quality));
+            let policy = job_policy(&db, cmd, &queue).await?;
+            let res = queue.enqueue(cmd.clone(), url.to_string(), kind, policy);
+            enqueue_text(&res)
+        }
+    };
+    // replacing text also removes the keyboard, so it can't be picked twice
+    bot.edit_message_text(picker.chat.id, picker.id, text)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_size, parse_quality_data, quality_data};
+    use crate::dl::yt_dlp::VideoQuality;
+
+    #[test]
+    fn test_quality_data() {
+        let quality = VideoQuality {
+            height: 1080,
+            ext: "mp4".to_string(),
+        };
+        assert_eq!(quality_data(&quality), "q:1080:mp4");
+        assert_eq!(parse_quality_data("q:1080:mp4"), Some(quality));
+        assert_eq!(parse_quality_data("q:1080:"), None);
+        assert_eq!(parse_quality_data("q:high:mp4"), None);
+        assert_eq!(parse_quality_data("approve:1"), None);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(1024 * 1024 * 25 / 2), "12.5 MB");
+        assert_eq!(format_size(1024 * 1024 * 1024 * 3), "3.0 GB");
+    }
+}
@@ -0,0 +1,512 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use teloxide::prelude::*;
+use teloxide::types::UserId;
+use tokio::sync::{Notify, Semaphore, SemaphorePermit};
+use tracing::{event, Level};
+
+use super::dl::bot_download;
+use super::playlist::{finish_item, Playlist, PlaylistItem};
+use super::quota::Quotas;
+use crate::db::download::DownloadStatus;
+use crate::db::DbPool;
+use crate::dl::cancel::CancelToken;
+use crate::dl::clip::Clip;
+use crate::dl::limits::Limits;
+use crate::dl::policy::FormatPolicy;
+use crate::dl::yt_dlp::{VideoQuality, YtDlp, YtDlpInfo, YtDlpPlaylist};
+use crate::dl::DownloadError;
+use crate::util::parse_env_or;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobKind {
+    // best quality if none picked
+    Video(Option<VideoQuality>),
+    Audio,
+    // part of video, always best quality
+    Clip(Clip),
+}
+
+pub struct Job {
+    pub id: i32,
+    pub kind: JobKind,
+    pub policy: FormatPolicy,
+    pub msg: Message,
+    pub url: String,
+    pub cancel: CancelToken,
+    pub playlist: Option<PlaylistItem>,
+}
+
+#[derive(Clone, Copy)]
+struct JobOwner {
+    user_id: Option<UserId>,
+    chat_id: ChatId,
+}
+
+impl JobOwner {
+    fn of(msg: &Message) -> Self {
+        Self {
+            user_id: msg.from().map(|user| user.id),
+            chat_id: msg.chat.id,
+        }
+    }
+}
+
+struct RunningJob {
+    owner: JobOwner,
+    cancel: CancelToken,
+    playlist: Option<Arc<Playlist>>,
+}
+
+#[derive(Debug)]
+pub enum QueueError {
+    UserLimit,
+    ChatLimit,
+}
+
+#[derive(Debug)]
+pub enum CancelError {
+    NotFound,
+    NotPermitted,
+}
+
+#[derive(Default)]
+struct QueueState {
+    next_id: i32,
+    pending: VecDeque<Job>,
+    running: HashMap<i32, RunningJob>,
+}
+
+impl QueueState {
+    // playlist counts as a single job, no matter how many items it has left
+    fn owners(&self) -> Vec<JobOwner> {
+        let jobs = self
+            .pending
+            .iter()
+            .map(|job| {
+                let playlist = job.playlist.as_ref().map(|item| &item.playlist);
+                (JobOwner::of(&job.msg), playlist)
+            })
+            .chain(
+                self.running
+                    .values()
+                    .map(|job| (job.owner, job.playlist.as_ref())),
+            );
+
+        let mut playlists: Vec<&Arc<Playlist>> = Vec::new();
+        let mut owners = Vec::new();
+        for (owner, playlist) in jobs {
+            if let Some(playlist) = playlist {
+                if playlists.iter().any(|seen| Arc::ptr_eq(seen, playlist)) {
+                    continue;
+                }
+                playlists.push(playlist);
+            }
+            owners.push(owner);
+        }
+
+        owners
+    }
+
+    fn is_running(&self, playlist: &Arc<Playlist>) -> bool {
+        self.running.values().any(|job| {
+            job.playlist
+                .as_ref()
+                .is_some_and(|running| Arc::ptr_eq(running, playlist))
+        })
+    }
+
+    fn owner_of(&self, id: i32) -> Option<JobOwner> {
+        match self.running.get(&id) {
+            Some(job) => Some(job.owner),
+            None => self
+                .pending
+                .iter()
+                .find(|job| job.id == id)
+                .map(|job| JobOwner::of(&job.msg)),
+        }
+    }
+
+    fn last_job_of(&self, user_id: UserId) -> Option<i32> {
+        self.pending
+            .iter()
+            .map(|job| (job.id, JobOwner::of(&job.msg)))
+            .chain(self.running.iter().map(|(id, job)| (*id, job.owner)))
+            .filter(|(_, owner)| owner.user_id == Some(user_id))
+            .map(|(id, _)| id)
+            .max()
+    }
+}
+
+struct QueueInner {
+    state: Mutex<QueueState>,
+    notify: Notify,
+    // shared by workers and info fetches, so no more than DL_WORKERS
+    // yt-dlp processes run at once
+    slots: Semaphore,
+    limits: Limits,
+    policy: FormatPolicy,
+    quotas: Quotas,
+    workers: usize,
+    user_limit: usize,
+    chat_limit: usize,
+}
+
+#[derive(Clone)]
+pub struct DownloadQueue {
+    inner: Arc<QueueInner>,
+}
+
+impl DownloadQueue {
+    pub fn new(
+        limits: Limits,
+        policy: FormatPolicy,
+        quotas: Quotas,
+        workers: usize,
+        user_limit: usize,
+        chat_limit: usize,
+    ) -> Self {
+        Self {
+            inner: Arc::new(QueueInner {
+                state: Mutex::new(QueueState::default()),
+                notify: Notify::new(),
+                slots: Semaphore::new(workers),
+                limits,
+                policy,
+                quotas,
+                workers,
+                user_limit,
+                chat_limit,
+            }),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(
+            Limits::from_env(),
+            FormatPolicy::from_env(),
+            Quotas::from_env(),
+            // no workers would leave every job waiting forever
+            parse_env_or::<usize>("DL_WORKERS", 2).max(1),
+            parse_env_or("DL_USER_JOB_LIMIT", 2),
+            parse_env_or("DL_CHAT_JOB_LIMIT", 5),
+        )
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.inner.limits
+    }
+
+    // global policy, chats may override it
+    pub fn policy(&self) -> &FormatPolicy {
+        &self.inner.policy
+    }
+
+    // global quotas, users and chats may override them
+    pub fn quotas(&self) -> Quotas {
+        self.inner.quotas
+    }
+
+    async fn acquire_slot(&self) -> SemaphorePermit<'_> {
+        self.inner
+            .slots
+            .acquire()
+            .await
+            .expect("slots semaphore is never closed")
+    }
+
+    fn check_limits(&self, state: &QueueState, owner: JobOwner) -> Result<(), QueueError> {
+        if let Some(user_id) = owner.user_id {
+            let jobs = state
+                .owners()
+                .iter()
+                .filter(|o| o.user_id == Some(user_id))
+                .count();
+            if jobs >= self.inner.user_limit {
+                return Err(QueueError::UserLimit);
+            }
+        }
+
+        let jobs = state
+            .owners()
+            .iter()
+            .filter(|o| o.chat_id == owner.chat_id)
+            .count();
+        if jobs >= self.inner.chat_limit {
+            return Err(QueueError::ChatLimit);
+        }
+
+        Ok(())
+    }
+
+    fn push(
+        state: &mut QueueState,
+        msg: Message,
+        url: String,
+        kind: JobKind,
+        policy: FormatPolicy,
+        playlist: Option<PlaylistItem>,
+    ) -> i32 {
+        state.next_id += 1;
+        let job = Job {
+            id: state.next_id,
+            kind,
+            policy,
+            msg,
+            url,
+            cancel: CancelToken::new(),
+            playlist,
+        };
+        let id = job.id;
+        event!(Level::INFO, "queued job {} for {}", id, job.url);
+        state.pending.push_back(job);
+
+        id
+    }
+
+    // returns job id and position in queue
+    pub fn enqueue(
+        &self,
+        msg: Message,
+        url: String,
+        kind: JobKind,
+        policy: FormatPolicy,
+    ) -> Result<(i32, usize), QueueError> {
+        let mut state = self.inner.state.lock().unwrap();
+        self.check_limits(&state, JobOwner::of(&msg))?;
+
+        let id = Self::push(&mut state, msg, url, kind, policy, None);
+        let position = state.pending.len();
+        drop(state);
+
+        self.inner.notify.notify_one();
+        Ok((id, position))
+    }
+
+    // whole playlist goes in at once and is checked against limits as a single job,
+    // its items are taken one at a time so it holds no more than one worker
+    pub fn enqueue_playlist(
+        &self,
+        msg: Message,
+        urls: Vec<String>,
+        kind: JobKind,
+        policy: FormatPolicy,
+        playlist: Playlist,
+    ) -> Result<Vec<i32>, QueueError> {
+        let mut state = self.inner.state.lock().unwrap();
+        self.check_limits(&state, JobOwner::of(&msg))?;
+
+        let playlist = Arc::new(playlist);
+        let ids: Vec<i32> = urls
+            .into_iter()
+            .enumerate()
+            .map(|(index, url)| {
+                let item = PlaylistItem {
+                    playlist: playlist.clone(),
+                    index,
+                };
+                Self::push(
+                    &mut state,
+                    msg.clone(),
+                    url,
+                    kind.clone(),
+                    policy.clone(),
+                    Some(item),
+                )
+            })
+            .collect();
+        drop(state);
+
+        self.inner.notify.notify_one();
+        Ok(ids)
+    }
+
+    // registers info fetch as running job, so it counts against limits
+    // and can be cancelled like any other download
+    pub fn start_info(&self, msg: &Message) -> Result<InfoJob, QueueError> {
+        let mut state = self.inner.state.lock().unwrap();
+        let owner = JobOwner::of(msg);
+        self.check_limits(&state, owner)?;
+
+        state.next_id += 1;
+        let id = state.next_id;
+        let cancel = CancelToken::new();
+        state.running.insert(
+            id,
+            RunningJob {
+                owner,
+                cancel: cancel.clone(),
+                playlist: None,
+            },
+        );
+        event!(Level::INFO, "started info job {}", id);
+
+        Ok(InfoJob {
+            queue: self.clone(),
+            id,
+            cancel,
+        })
+    }
+
+    // cancels job by id, or the latest job of user if no id given.
+    // only owner of the job or admin can cancel it
+    pub fn cancel(
+        &self,
+        id: Option<i32>,
+        user_id: UserId,
+        is_admin: bool,
+    ) -> Result<i32, CancelError> {
+        let mut state = self.inner.state.lock().unwrap();
+
+        let id = match id {
+            Some(id) => id,
+            None => state.last_job_of(user_id).ok_or(CancelError::NotFound)?,
+        };
+        let owner = state.owner_of(id).ok_or(CancelError::NotFound)?;
+        if owner.user_id != Some(user_id) && !is_admin {
+            return Err(CancelError::NotPermitted);
+        }
+
+        if let Some(job) = state.running.get(&id) {
+            job.cancel.cancel();
+        } else if let Some(job) = state
+            .pending
+            .iter()
+            .find(|job| job.id == id && job.playlist.is_some())
+        {
+            // playlist items still have to report result, or summary would never be posted
+            job.cancel.cancel();
+        } else {
+            state.pending.retain(|job| job.id != id);
+        }
+        event!(Level::INFO, "cancelled job {}", id);
+
+        Ok(id)
+    }
+
+    // skips items of playlists that already have one running
+    fn take(&self) -> Option<Job> {
+        let mut state = self.inner.state.lock().unwrap();
+        let index = state.pending.iter().position(|job| match &job.playlist {
+            Some(item) => !state.is_running(&item.playlist),
+            None => true,
+        })?;
+        let job = state.pending.remove(index)?;
+        state.running.insert(
+            job.id,
+            RunningJob {
+                owner: JobOwner::of(&job.msg),
+                cancel: job.cancel.clone(),
+                playlist: job.playlist.as_ref().map(|item| item.playlist.clone()),
+            },
+        );
+
+        Some(job)
+    }
+
+    fn finish(&self, id: i32) {
+        self.inner.state.lock().unwrap().running.remove(&id);
+    }
+
+    pub fn start(&self, bot: Bot, db: DbPool) {
+        event!(
+            Level::INFO,
+            "starting {} download workers",
+            self.inner.workers
+        );
+        for _ in 0..self.inner.workers {
+            tokio::spawn(self.clone().worker(bot.clone(), db.clone()));
+        }
+    }
+
+    async fn worker(self, bot: Bot, db: DbPool) {
+        loop {
+            let slot = self.acquire_slot().await;
+            let job = match self.take() {
+                Some(job) => job,
+                None => {
+                    // slot is freed while idle, so info fetches can use it
+                    drop(slot);
+                    self.inner.notify.notified().await;
+                    continue;
+                }
+            };
+
+            let id = job.id;
+            let playlist = job.playlist.clone();
+            event!(Level::INFO, "started job {}", id);
+            // separate task, so panic in the job won't take down the worker
+            let task = bot_download(bot.clone(), db.clone(), job, self.clone());
+            match tokio::spawn(task).await {
+                Ok(Ok(())) => event!(Level::INFO, "finished job {}", id),
+                Ok(Err(e)) => event!(Level::ERROR, "job {} error {}", id, e),
+                Err(e) => event!(Level::ERROR, "job {} panicked {}", id, e),
+            }
+            // no-op if the job reported its result itself
+            if let Some(item) = playlist {
+                if let Err(e) = finish_item(&bot, &item, DownloadStatus::Failed).await {
+                    event!(Level::ERROR, "job {} playlist error {}", id, e);
+                }
+            }
+
+            self.finish(id);
+            drop(slot);
+        }
+    }
+}
+
+// info fetch outside of the queue, like for quality picker. stays running until dropped
+pub struct InfoJob {
+    queue: DownloadQueue,
+    id: i32,
+    cancel: CancelToken,
+}
+
+impl InfoJob {
+    // waits for a free slot, cancelling stops the wait as well
+    async fn acquire_slot(&self) -> Result<SemaphorePermit<'_>, DownloadError> {
+        tokio::select! {
+            slot = self.queue.acquire_slot() => Ok(slot),
+            _ = self.cancel.cancelled() => Err(DownloadError::Cancelled),
+        }
+    }
+
+    pub async fn load_info(&self, url: &str) -> Result<YtDlpInfo, DownloadError> {
+        let limits = self.queue.limits();
+        let _slot = self.acquire_slot().await?;
+
+        let info = YtDlp::load_info(
+            url,
+            limits.max_gallery_items,
+            &self.cancel,
+            limits.info_timeout,
+        )
+        .await?;
+        if info.is_playlist() {
+            return Err(DownloadError::Playlist);
+        }
+
+        Ok(info)
+    }
+
+    pub async fn load_playlist(
+        &self,
+        url: &str,
+        start: usize,
+        end: Option<usize>,
+    ) -> Result<YtDlpPlaylist, DownloadError> {
+        let limits = self.queue.limits();
+        let _slot = self.acquire_slot().await?;
+
+        let playlist =
+            YtDlp::load_playlist(url, start, end, &self.cancel, limits.info_timeout).await?;
+        Ok(playlist)
+    }
+}
+
+impl Drop for InfoJob {
+    fn drop(&mut self) {
+        self.queue.finish(self.id);
+        event!(Level::INFO, "finished info job {}", self.id);
+    }
+}
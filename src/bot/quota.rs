@@ -0,0 +1,373 @@
+use rust_i18n::t;
+use std::fmt;
+use teloxide::prelude::*;
+use tracing::{event, Level};
+
+use super::quality::format_size;
+use super::queue::DownloadQueue;
+use super::types::HandlerResult;
+use crate::db::chat::{find_or_create_chat, update_chat_quota};
+use crate::db::download::{chat_usage, user_usage, Usage};
+use crate::db::user::{find_or_create_user, update_user_quota};
+use crate::db::{DbPool, QuotaOverride};
+use crate::reply_i18n_and_return;
+use crate::util::{parse_env_or, parse_limit};
+
+// counted over last hour and last day, 0 means unlimited
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quota {
+    pub per_hour: u32,
+    pub per_day: u32,
+    // bytes
+    pub bytes_per_day: u64,
+}
+
+fn limit_text(limit: u64, fmt: impl Fn(u64) -> String) -> String {
+    if limit == 0 {
+        "-".to_string()
+    } else {
+        fmt(limit)
+    }
+}
+
+impl fmt::Display for Quota {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "hour {}\nday {}\nbytes {}",
+            limit_text(self.per_hour as u64, |v| v.to_string()),
+            limit_text(self.per_day as u64, |v| v.to_string()),
+            limit_text(self.bytes_per_day, format_size)
+        )
+    }
+}
+
+impl Quota {
+    fn from_env(prefix: &str, default: Self) -> Self {
+        Self {
+            per_hour: parse_env_or(&format!("{}_HOUR", prefix), default.per_hour),
+            per_day: parse_env_or(&format!("{}_DAY", prefix), default.per_day),
+            bytes_per_day: parse_env_or(&format!("{}_BYTES", prefix), default.bytes_per_day),
+        }
+    }
+
+    pub fn with_override(&self, quota: &QuotaOverride) -> Self {
+        Self {
+            per_hour: quota.quota_hour.map_or(self.per_hour, |v| v.max(0) as u32),
+            per_day: quota.quota_day.map_or(self.per_day, |v| v.max(0) as u32),
+            bytes_per_day: quota
+                .quota_bytes
+                .map_or(self.bytes_per_day, |v| v.max(0) as u64),
+        }
+    }
+
+    pub fn exceeded(&self, hour: &Usage, day: &Usage) -> bool {
+        fn over(limit: u64, used: i64) -> bool {
+            limit > 0 && used >= limit as i64
+        }
+
+        over(self.per_hour as u64, hour.downloads)
+            || over(self.per_day as u64, day.downloads)
+            || over(self.bytes_per_day, day.bytes)
+    }
+
+    // downloads left before hour or day limit is hit, None if there's no limit
+    pub fn remaining(&self, hour: &Usage, day: &Usage) -> Option<u32> {
+        fn left(limit: u32, used: i64) -> Option<u32> {
+            (limit > 0).then(|| (limit as i64 - used).max(0) as u32)
+        }
+
+        [
+            left(self.per_hour, hour.downloads),
+            left(self.per_day, day.downloads),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+
+    fn usage_text(&self, hour: &Usage, day: &Usage) -> String {
+        format!(
+            "hour {}/{}\nday {}/{}\nbytes {}/{}",
+            hour.downloads,
+            limit_text(self.per_hour as u64, |v| v.to_string()),
+            day.downloads,
+            limit_text(self.per_day as u64, |v| v.to_string()),
+            format_size(day.bytes as u64),
+            limit_text(self.bytes_per_day, format_size)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Quotas {
+    pub user: Quota,
+    // shared by all users of a group chat
+    pub chat: Quota,
+}
+
+impl Quotas {
+    pub fn from_env() -> Self {
+        const GB: u64 = 1024 * 1024 * 1024;
+        Self {
+            user: Quota::from_env(
+                "DL_USER_QUOTA",
+                Quota {
+                    per_hour: 10,
+                    per_day: 50,
+                    bytes_per_day: 2 * GB,
+                },
+            ),
+            chat: Quota::from_env(
+                "DL_CHAT_QUOTA",
+                Quota {
+                    per_hour: 30,
+                    per_day: 200,
+                    bytes_per_day: 10 * GB,
+                },
+            ),
+        }
+    }
+}
+
+struct QuotaUsage {
+    // reply key for when it's exceeded
+    exceeded_key: &'static str,
+    quota: Quota,
+    hour: Usage,
+    day: Usage,
+}
+
+// quotas of the user and the group chat with their usage, admins have no quota
+async fn quota_usages(
+    db: &DbPool,
+    msg: &Message,
+    queue: &DownloadQueue,
+) -> Result<Vec<QuotaUsage>, sqlx::Error> {
+    let mut usages = Vec::new();
+    if let Some(user) = msg.from() {
+        let user = find_or_create_user(db, user).await?;
+        if user.is_admin {
+            return Ok(usages);
+        }
+
+        usages.push(QuotaUsage {
+            exceeded_key: "quota_user_exceeded",
+            quota: queue.quotas().user.with_override(&user.quota),
+            hour: user_usage(db, user.id, 1).await?,
+            day: user_usage(db, user.id, 24).await?,
+        });
+    }
+
+    if !msg.chat.is_private() {
+        let chat = find_or_create_chat(db, &msg.chat).await?;
+        usages.push(QuotaUsage {
+            exceeded_key: "quota_chat_exceeded",
+            quota: queue.quotas().chat.with_override(&chat.quota),
+            hour: chat_usage(db, chat.tg_id, 1).await?,
+            day: chat_usage(db, chat.tg_id, 24).await?,
+        });
+    }
+
+    Ok(usages)
+}
+
+pub async fn quota_denied(
+    db: &DbPool,
+    msg: &Message,
+    queue: &DownloadQueue,
+) -> Result<Option<&'static str>, sqlx::Error> {
+    Ok(quota_usages(db, msg, queue)
+        .await?
+        .into_iter()
+        .find(|usage| usage.quota.exceeded(&usage.hour, &usage.day))
+        .map(|usage| usage.exceeded_key))
+}
+
+// how many more downloads fit in user and chat quotas, along with reply key
+// of the quota that allows the fewest. None if unlimited
+pub async fn quota_remaining(
+    db: &DbPool,
+    msg: &Message,
+    queue: &DownloadQueue,
+) -> Result<Option<(u32, &'static str)>, sqlx::Error> {
+    Ok(quota_usages(db, msg, queue)
+        .await?
+        .iter()
+        .filter_map(|usage| {
+            let remaining = usage.quota.remaining(&usage.hour, &usage.day)?;
+            Some((remaining, usage.exceeded_key))
+        })
+        .min_by_key(|(remaining, _)| *remaining))
+}
+
+fn set_override(quota: &mut QuotaOverride, key: &str, value: &str) -> Option<()> {
+    match key {
+        // 0 means unlimited
+        "hour" => quota.quota_hour = parse_limit(value, true)?,
+        "day" => quota.quota_day = parse_limit(value, true)?,
+        "bytes" => quota.quota_bytes = parse_limit(value, true)?,
+        _ => return None,
+    }
+
+    Some(())
+}
+
+// /quota - own quota, admins can reply to a message to see quota of its author
+pub async fn cmd_quota(bot: Bot, msg: Message, db: DbPool, queue: DownloadQueue) -> HandlerResult {
+    if let Some(user) = msg.from() {
+        let mut user = find_or_create_user(&db, user).await?;
+        if let Some(target) = msg.reply_to_message().and_then(|m| m.from()) {
+            if !user.is_admin {
+                reply_i18n_and_return!(bot, msg.chat.id, "not_an_admin");
+            }
+            user = find_or_create_user(&db, target).await?;
+        }
+
+        let quota = queue.quotas().user.with_override(&user.quota);
+        let hour = user_usage(&db, user.id, 1).await?;
+        let day = user_usage(&db, user.id, 24).await?;
+        let mut text = t!(
+            "quota_user",
+            user = user.to_string(),
+            quota = quota.usage_text(&hour, &day)
+        )
+        .to_string();
+
+        if !msg.chat.is_private() {
+            let chat = find_or_create_chat(&db, &msg.chat).await?;
+            let quota = queue.quotas().chat.with_override(&chat.quota);
+            let hour = chat_usage(&db, chat.tg_id, 1).await?;
+            let day = chat_usage(&db, chat.tg_id, 24).await?;
+            text.push_str("\n\n");
+            text.push_str(&t!(
+                "quota_chat",
+                chat = chat.to_string(),
+                quota = quota.usage_text(&hour, &day)
+            ));
+        }
+
+        bot.send_message(msg.chat.id, text).await?;
+    }
+
+    Ok(())
+}
+
+// /setquota <hour|day|bytes> <value|default> - sets quota of chat,
+// or of message author when replying
+pub async fn cmd_setquota(
+    bot: Bot,
+    msg: Message,
+    text: String,
+    db: DbPool,
+    queue: DownloadQueue,
+) -> HandlerResult {
+    if let Some(user) = msg.from() {
+        let user = find_or_create_user(&db, user).await?;
+        if !user.is_admin {
+            reply_i18n_and_return!(bot, msg.chat.id, "not_an_admin");
+        }
+
+        let mut args = text.split_whitespace();
+        let (key, value) = match (args.next(), args.next(), args.next()) {
+            (Some(key), Some(value), None) => (key, value),
+            _ => {
+                reply_i18n_and_return!(bot, msg.chat.id, "setquota_usage");
+            }
+        };
+
+        let quota = if let Some(target) = msg.reply_to_message().and_then(|m| m.from()) {
+            let mut target = find_or_create_user(&db, target).await?;
+            if set_override(&mut target.quota, key, value).is_none() {
+                reply_i18n_and_return!(bot, msg.chat.id, "setquota_usage");
+            }
+
+            update_user_quota(&db, &target).await?;
+            event!(
+                Level::INFO,
+                "quota of {} set to {} by {}",
+                target,
+                text,
+                user
+            );
+            queue.quotas().user.with_override(&target.quota)
+        } else if !msg.chat.is_private() {
+            let mut chat = find_or_create_chat(&db, &msg.chat).await?;
+            if set_override(&mut chat.quota, key, value).is_none() {
+                reply_i18n_and_return!(bot, msg.chat.id, "setquota_usage");
+            }
+
+            update_chat_quota(&db, &chat).await?;
+            event!(Level::INFO, "quota of {} set to {} by {}", chat, text, user);
+            queue.quotas().chat.with_override(&chat.quota)
+        } else {
+            reply_i18n_and_return!(bot, msg.chat.id, "setquota_usage");
+        };
+
+        bot.send_message(msg.chat.id, t!("quota_set", quota = quota.to_string()))
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{set_override, Quota};
+    use crate::db::download::Usage;
+    use crate::db::QuotaOverride;
+
+    #[test]
+    fn test_quota_override() {
+        let global = Quota {
+            per_hour: 10,
+            per_day: 50,
+            bytes_per_day: 1000,
+        };
+        let mut quota = QuotaOverride::default();
+        assert_eq!(global.with_override(&quota), global);
+
+        assert!(set_override(&mut quota, "hour", "0").is_some());
+        assert!(set_override(&mut quota, "bytes", "500").is_some());
+        assert!(set_override(&mut quota, "day", "-1").is_none());
+        assert!(set_override(&mut quota, "week", "5").is_none());
+        let custom = global.with_override(&quota);
+        assert_eq!(custom.per_hour, 0);
+        assert_eq!(custom.per_day, 50);
+        assert_eq!(custom.bytes_per_day, 500);
+
+        assert!(set_override(&mut quota, "bytes", "default").is_some());
+        assert_eq!(global.with_override(&quota).bytes_per_day, 1000);
+    }
+
+    #[test]
+    fn test_quota_exceeded() {
+        let quota = Quota {
+            per_hour: 0,
+            per_day: 5,
+            bytes_per_day: 1000,
+        };
+        let usage = |downloads, bytes| Usage { downloads, bytes };
+        assert!(!quota.exceeded(&usage(100, 0), &usage(4, 999)));
+        assert!(quota.exceeded(&usage(1, 0), &usage(5, 0)));
+        assert!(quota.exceeded(&usage(1, 0), &usage(1, 1000)));
+    }
+
+    #[test]
+    fn test_quota_remaining() {
+        let quota = |per_hour, per_day| Quota {
+            per_hour,
+            per_day,
+            bytes_per_day: 0,
+        };
+        let usage = |downloads| Usage {
+            downloads,
+            bytes: 0,
+        };
+        assert_eq!(quota(10, 50).remaining(&usage(9), &usage(20)), Some(1));
+        assert_eq!(quota(10, 50).remaining(&usage(0), &usage(45)), Some(5));
+        assert_eq!(quota(10, 50).remaining(&usage(12), &usage(20)), Some(0));
+        assert_eq!(quota(0, 50).remaining(&usage(100), &usage(20)), Some(30));
+        assert_eq!(quota(0, 0).remaining(&usage(100), &usage(100)), None);
+    }
+}
@@ -1,15 +1,53 @@
+use chrono::{DateTime, Duration, Utc};
 use rust_i18n::t;
-use sqlx::Row;
 use teloxide::prelude::*;
-use teloxide::types::Recipient;
 use tracing::{event, Level};
 
-use super::notify::notify_admins;
-use super::types::HandlerResult;
+use super::notify::{notify_admins, notify_requester, resolve_notifications, RequestKind};
+use super::types::{HandlerErr, HandlerResult};
+use crate::db::request::{decide_request, has_pending_request, last_declined_at, RequestStatus};
 use crate::db::user::find_or_create_user;
 use crate::db::{DbPool, User};
+use crate::util::parse_env_or;
 use crate::{parse_integer, reply_i18n_and_return};
 
+// how long declined user or chat has to wait before requesting again
+fn request_cooldown() -> Duration {
+    Duration::hours(parse_env_or("REQUEST_COOLDOWN_HOURS", 24))
+}
+
+// None if cooldown is already over
+fn retry_after(
+    declined_at: DateTime<Utc>,
+    cooldown: Duration,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    let retry_at = declined_at + cooldown;
+    (now < retry_at).then_some(retry_at)
+}
+
+// tells declined requester when they may request again, None if they already may
+pub fn cooldown_text(declined_at: Option<DateTime<Utc>>) -> Option<String> {
+    let retry_at = retry_after(declined_at?, request_cooldown(), Utc::now())?;
+    let time = retry_at.format("%Y-%m-%d %H:%M UTC").to_string();
+    Some(t!("request_cooldown", time = time).to_string())
+}
+
+// /decline <id> [reason]
+pub fn split_reason(text: &str) -> (&str, Option<&str>) {
+    match text.trim().split_once(char::is_whitespace) {
+        Some((id, reason)) => (id, Some(reason.trim())),
+        None => (text.trim(), None),
+    }
+}
+
+pub fn with_reason(text: &str, reason: Option<&str>) -> String {
+    match reason {
+        Some(reason) => format!("{}\n{}", text, t!("decline_reason", reason = reason)),
+        None => text.to_string(),
+    }
+}
+
 pub async fn cmd_request(bot: Bot, msg: Message, text: String, db: DbPool) -> HandlerResult {
     if text.len() < 16 {
         reply_i18n_and_return!(bot, msg.chat.id, "request_text_is_too_short");
@@ -23,32 +61,31 @@ pub async fn cmd_request(bot: Bot, msg: Message, text: String, db: DbPool) -> Ha
             reply_i18n_and_return!(bot, msg.chat.id, "already_can_download");
         }
 
-        let requests: i64 =
-            sqlx::query(r#"SELECT COUNT(1) FROM "request" WHERE requested_by = $1;"#)
-                .bind(user.id)
-                .fetch_one(&db)
-                .await?
-                .get(0);
-        if requests > 0 {
+        if has_pending_request(&db, user.id).await? {
             reply_i18n_and_return!(bot, msg.chat.id, "already_has_requested");
         }
+        if let Some(text) = cooldown_text(last_declined_at(&db, user.id).await?) {
+            bot.send_message(msg.chat.id, text).await?;
+            return Ok(());
+        }
 
         // put the request
-        sqlx::query(
-            r#"INSERT INTO "request" (requested_by,message,is_approved) VALUES ($1,$2,$3);"#,
+        let request_id: i32 = sqlx::query_scalar(
+            r#"INSERT INTO "request" (requested_by,message) VALUES ($1,$2) RETURNING id;"#,
         )
         .bind(user.id)
         .bind(text)
-        .bind(false)
-        .execute(&db)
+        .fetch_one(&db)
         .await?;
-        event!(Level::INFO, "added request for {}", user);
+        event!(Level::INFO, "added request {} for {}", request_id, user);
 
         // notify admins
         notify_admins(
             &bot,
             &db,
             t!("admin_notify_request", user = user.to_string()).to_string(),
+            RequestKind::User,
+            request_id,
         )
         .await?;
 
@@ -77,8 +114,9 @@ pub async fn cmd_listrequests(bot: Bot, msg: Message, db: DbPool) -> HandlerResu
             r#"SELECT "request".id AS request_id, "request".message, "user".*
             FROM "request"
             INNER JOIN "user" ON "request".requested_by = "user".id
-            WHERE "request".is_approved = false;"#,
+            WHERE "request".status = $1;"#,
         )
+        .bind(RequestStatus::Pending.as_str())
         .fetch_all(&db)
         .await?;
 
@@ -97,6 +135,107 @@ pub async fn cmd_listrequests(bot: Bot, msg: Message, db: DbPool) -> HandlerResu
     Ok(())
 }
 
+async fn find_pending_request(
+    db: &DbPool,
+    id: i32,
+) -> Result<Option<RequestWithUser>, sqlx::Error> {
+    sqlx::query_as(
+        r#"SELECT "request".id AS request_id, "request".message, "user".*
+            FROM "request"
+            INNER JOIN "user" ON "request".requested_by = "user".id
+            WHERE "request".id = $1 AND "request".status = $2
+            LIMIT 1;"#,
+    )
+    .bind(id)
+    .bind(RequestStatus::Pending.as_str())
+    .fetch_optional(db)
+    .await
+}
+
+// approves request and notifies everyone involved, returns reply for admin
+pub async fn approve_request(
+    bot: &Bot,
+    db: &DbPool,
+    admin: &User,
+    id: i32,
+) -> Result<&'static str, HandlerErr> {
+    let request = match find_pending_request(db, id).await? {
+        Some(request) => request,
+        None => return Ok("request_not_found"),
+    };
+
+    // approve request
+    let status = RequestStatus::Approved;
+    if !decide_request(db, request.request_id, status, admin.id, None).await? {
+        return Ok("request_not_found");
+    }
+    event!(
+        Level::INFO,
+        "approved request {} by {} for {}",
+        request.request_id,
+        admin,
+        request.user
+    );
+
+    // notify target user
+    if request.user.has_private_chat {
+        let text = t!("your_request_approved").to_string();
+        notify_requester(bot, ChatId(request.user.tg_id), text).await;
+    }
+
+    let text = t!(
+        "admin_request_approved",
+        user = request.user.to_string(),
+        admin = admin.to_string()
+    );
+    resolve_notifications(bot, db, RequestKind::User, request.request_id, &text).await?;
+
+    Ok("request_approved")
+}
+
+// declines request and notifies everyone involved, returns reply for admin
+pub async fn decline_request(
+    bot: &Bot,
+    db: &DbPool,
+    admin: &User,
+    id: i32,
+    reason: Option<&str>,
+) -> Result<&'static str, HandlerErr> {
+    let request = match find_pending_request(db, id).await? {
+        Some(request) => request,
+        None => return Ok("request_not_found"),
+    };
+
+    // decline request
+    let status = RequestStatus::Declined;
+    if !decide_request(db, request.request_id, status, admin.id, reason).await? {
+        return Ok("request_not_found");
+    }
+    event!(
+        Level::INFO,
+        "declined request {} by {} for {}",
+        request.request_id,
+        admin,
+        request.user
+    );
+
+    // notify target user
+    if request.user.has_private_chat {
+        let text = with_reason(&t!("your_request_declined"), reason);
+        notify_requester(bot, ChatId(request.user.tg_id), text).await;
+    }
+
+    let text = t!(
+        "admin_request_declined",
+        user = request.user.to_string(),
+        admin = admin.to_string()
+    );
+    let text = with_reason(&text, reason);
+    resolve_notifications(bot, db, RequestKind::User, request.request_id, &text).await?;
+
+    Ok("request_declined")
+}
+
 pub async fn cmd_approve(bot: Bot, msg: Message, id: String, db: DbPool) -> HandlerResult {
     let id: i32 = parse_integer!(bot, msg.chat.id, id);
 
@@ -106,59 +245,15 @@ pub async fn cmd_approve(bot: Bot, msg: Message, id: String, db: DbPool) -> Hand
             reply_i18n_and_return!(bot, msg.chat.id, "not_an_admin");
         }
 
-        // get request
-        let res: Result<RequestWithUser, sqlx::Error> = sqlx::query_as(
-            r#"SELECT "request".id AS request_id, "request".message, "user".*
-                FROM "request"
-                INNER JOIN "user" ON "request".requested_by = "user".id
-                WHERE "request".id = $1 AND "request".is_approved = false
-                LIMIT 1;"#,
-        )
-        .bind(id)
-        .fetch_one(&db)
-        .await;
-        let request = match res {
-            Ok(request) => request,
-            Err(e) => match e {
-                sqlx::Error::RowNotFound => {
-                    bot.send_message(msg.chat.id, t!("request_not_found"))
-                        .await?;
-                    return Ok(());
-                }
-                _ => return Err(Box::new(e)),
-            },
-        };
-
-        // approve request
-        sqlx::query(r#"UPDATE "request" SET approved_by = $1, is_approved = true WHERE id = $2;"#)
-            .bind(user.id)
-            .bind(request.request_id)
-            .execute(&db)
-            .await?;
-        event!(
-            Level::INFO,
-            "approved request {} by {} for {}",
-            request.request_id,
-            user,
-            request.user
-        );
-        bot.send_message(msg.chat.id, t!("request_approved"))
-            .await?;
-
-        // notify target user
-        if request.user.has_private_chat {
-            bot.send_message(
-                Recipient::Id(ChatId(request.user.tg_id)),
-                t!("your_request_approved"),
-            )
-            .await?;
-        }
+        let reply = approve_request(&bot, &db, &user, id).await?;
+        bot.send_message(msg.chat.id, t!(reply)).await?;
     }
 
     Ok(())
 }
 
-pub async fn cmd_decline(bot: Bot, msg: Message, id: String, db: DbPool) -> HandlerResult {
+pub async fn cmd_decline(bot: Bot, msg: Message, text: String, db: DbPool) -> HandlerResult {
+    let (id, reason) = split_reason(&text);
     let id: i32 = parse_integer!(bot, msg.chat.id, id);
 
     if let Some(user) = msg.from() {
@@ -167,53 +262,39 @@ pub async fn cmd_decline(bot: Bot, msg: Message, id: String, db: DbPool) -> Hand
             reply_i18n_and_return!(bot, msg.chat.id, "not_an_admin");
         }
 
-        // get request
-        let res: Result<RequestWithUser, sqlx::Error> = sqlx::query_as(
-            r#"SELECT "request".id AS request_id, "request".message, "user".*
-                FROM "request"
-                INNER JOIN "user" ON "request".requested_by = "user".id
-                WHERE "request".id = $1 AND "request".is_approved = false
-                LIMIT 1;"#,
-        )
-        .bind(id)
-        .fetch_one(&db)
-        .await;
-        let request = match res {
-            Ok(request) => request,
-            Err(e) => match e {
-                sqlx::Error::RowNotFound => {
-                    bot.send_message(msg.chat.id, t!("request_not_found"))
-                        .await?;
-                    return Ok(());
-                }
-                _ => return Err(Box::new(e)),
-            },
-        };
-
-        // decline request
-        sqlx::query(r#"DELETE FROM "request" WHERE id = $1;"#)
-            .bind(request.request_id)
-            .execute(&db)
-            .await?;
-        event!(
-            Level::INFO,
-            "declined request {} by {} for {}",
-            request.request_id,
-            user,
-            request.user
-        );
-        bot.send_message(msg.chat.id, t!("request_declined"))
-            .await?;
-
-        // notify target user
-        if request.user.has_private_chat {
-            bot.send_message(
-                Recipient::Id(ChatId(request.user.tg_id)),
-                t!("your_request_declined"),
-            )
-            .await?;
-        }
+        let reply = decline_request(&bot, &db, &user, id, reason).await?;
+        bot.send_message(msg.chat.id, t!(reply)).await?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+
+    use super::{retry_after, split_reason};
+
+    #[test]
+    fn test_split_reason() {
+        assert_eq!(split_reason("12"), ("12", None));
+        assert_eq!(
+            split_reason(" 12  post a real reason "),
+            ("12", Some("post a real reason"))
+        );
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let declined_at = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let cooldown = Duration::hours(24);
+        assert_eq!(
+            retry_after(declined_at, cooldown, declined_at + Duration::hours(1)),
+            Some(declined_at + cooldown)
+        );
+        assert_eq!(
+            retry_after(declined_at, cooldown, declined_at + Duration::hours(25)),
+            None
+        );
+    }
+}
@@ -1,14 +1,16 @@
 use rust_i18n::t;
-use sqlx::Row;
 use teloxide::prelude::*;
-use teloxide::types::Recipient;
 use tracing::{event, Level};
 
-use super::notify::notify_admins;
-use super::types::HandlerResult;
+use super::notify::{notify_admins, notify_requester, resolve_notifications, RequestKind};
+use super::request::{cooldown_text, split_reason, with_reason};
+use super::types::{HandlerErr, HandlerResult};
 use crate::db::chat::find_or_create_chat;
+use crate::db::request::{
+    decide_chat_request, has_pending_chat_request, last_chat_declined_at, RequestStatus,
+};
 use crate::db::user::find_or_create_user;
-use crate::db::{Chat, DbPool};
+use crate::db::{Chat, DbPool, User};
 use crate::{parse_integer, reply_i18n_and_return};
 
 pub async fn cmd_request_chat(bot: Bot, msg: Message, text: String, db: DbPool) -> HandlerResult {
@@ -26,31 +28,38 @@ pub async fn cmd_request_chat(bot: Bot, msg: Message, text: String, db: DbPool)
             reply_i18n_and_return!(bot, msg.chat.id, "chat_already_can_download");
         }
 
-        let requests: i64 =
-            sqlx::query(r#"SELECT COUNT(1) FROM "request_chat" WHERE requested_for = $1;"#)
-                .bind(chat.id)
-                .fetch_one(&db)
-                .await?
-                .get(0);
-        if requests > 0 {
+        if has_pending_chat_request(&db, chat.id).await? {
             reply_i18n_and_return!(bot, msg.chat.id, "chat_already_has_requested");
         }
+        if let Some(text) = cooldown_text(last_chat_declined_at(&db, chat.id).await?) {
+            bot.send_message(msg.chat.id, text).await?;
+            return Ok(());
+        }
 
         // put the chat request
-        sqlx::query(r#"INSERT INTO "request_chat" (requested_by,requested_for,message,is_approved) VALUES ($1,$2,$3,$4);"#)
-            .bind(user.id)
-            .bind(chat.id)
-            .bind(text)
-            .bind(false)
-            .execute(&db)
-            .await?;
-        event!(Level::INFO, "added chat request for {}", chat);
+        let request_id: i32 = sqlx::query_scalar(
+            r#"INSERT INTO "request_chat" (requested_by,requested_for,message)
+            VALUES ($1,$2,$3) RETURNING id;"#,
+        )
+        .bind(user.id)
+        .bind(chat.id)
+        .bind(text)
+        .fetch_one(&db)
+        .await?;
+        event!(
+            Level::INFO,
+            "added chat request {} for {}",
+            request_id,
+            chat
+        );
 
         // notify admins
         notify_admins(
             &bot,
             &db,
             t!("admin_notify_chat_request", chat = chat.to_string()).to_string(),
+            RequestKind::Chat,
+            request_id,
         )
         .await?;
 
@@ -80,8 +89,9 @@ pub async fn cmd_listrequests_chat(bot: Bot, msg: Message, db: DbPool) -> Handle
             r#"SELECT "request_chat".id AS request_id, "request_chat".message, "chat".*
             FROM "request_chat"
             INNER JOIN "chat" ON "request_chat".requested_for = "chat".id
-            WHERE "request_chat".is_approved = false;"#,
+            WHERE "request_chat".status = $1;"#,
         )
+        .bind(RequestStatus::Pending.as_str())
         .fetch_all(&db)
         .await?;
 
@@ -100,6 +110,103 @@ pub async fn cmd_listrequests_chat(bot: Bot, msg: Message, db: DbPool) -> Handle
     Ok(())
 }
 
+async fn find_pending_chat_request(
+    db: &DbPool,
+    id: i32,
+) -> Result<Option<RequestChatWithChat>, sqlx::Error> {
+    sqlx::query_as(
+        r#"SELECT "request_chat".id AS request_id, "request_chat".message, "chat".*
+        FROM "request_chat"
+        INNER JOIN "chat" ON "request_chat".requested_for = "chat".id
+        WHERE "request_chat".id = $1 AND "request_chat".status = $2
+        LIMIT 1;"#,
+    )
+    .bind(id)
+    .bind(RequestStatus::Pending.as_str())
+    .fetch_optional(db)
+    .await
+}
+
+// approves chat request and notifies everyone involved, returns reply for admin
+pub async fn approve_chat_request(
+    bot: &Bot,
+    db: &DbPool,
+    admin: &User,
+    id: i32,
+) -> Result<&'static str, HandlerErr> {
+    let request = match find_pending_chat_request(db, id).await? {
+        Some(request) => request,
+        None => return Ok("chat_request_not_found"),
+    };
+
+    // approve request
+    let status = RequestStatus::Approved;
+    if !decide_chat_request(db, request.request_id, status, admin.id, None).await? {
+        return Ok("chat_request_not_found");
+    }
+    event!(
+        Level::INFO,
+        "approved chat request {} by {} for {}",
+        request.request_id,
+        admin,
+        request.chat
+    );
+
+    // notify target chat
+    let text = t!("chat_request_approved").to_string();
+    notify_requester(bot, ChatId(request.chat.tg_id), text).await;
+
+    let text = t!(
+        "admin_chat_request_approved",
+        chat = request.chat.to_string(),
+        admin = admin.to_string()
+    );
+    resolve_notifications(bot, db, RequestKind::Chat, request.request_id, &text).await?;
+
+    Ok("chat_request_approved_reply")
+}
+
+// declines chat request and notifies everyone involved, returns reply for admin
+pub async fn decline_chat_request(
+    bot: &Bot,
+    db: &DbPool,
+    admin: &User,
+    id: i32,
+    reason: Option<&str>,
+) -> Result<&'static str, HandlerErr> {
+    let request = match find_pending_chat_request(db, id).await? {
+        Some(request) => request,
+        None => return Ok("chat_request_not_found"),
+    };
+
+    // decline request
+    let status = RequestStatus::Declined;
+    if !decide_chat_request(db, request.request_id, status, admin.id, reason).await? {
+        return Ok("chat_request_not_found");
+    }
+    event!(
+        Level::INFO,
+        "declined request {} by {} for {}",
+        request.request_id,
+        admin,
+        request.chat
+    );
+
+    // notify target chat
+    let text = with_reason(&t!("chat_request_declined"), reason);
+    notify_requester(bot, ChatId(request.chat.tg_id), text).await;
+
+    let text = t!(
+        "admin_chat_request_declined",
+        chat = request.chat.to_string(),
+        admin = admin.to_string()
+    );
+    let text = with_reason(&text, reason);
+    resolve_notifications(bot, db, RequestKind::Chat, request.request_id, &text).await?;
+
+    Ok("request_declined")
+}
+
 pub async fn cmd_approve_chat(bot: Bot, msg: Message, id: String, db: DbPool) -> HandlerResult {
     let id: i32 = parse_integer!(bot, msg.chat.id, id);
 
@@ -109,56 +216,15 @@ pub async fn cmd_approve_chat(bot: Bot, msg: Message, id: String, db: DbPool) ->
             reply_i18n_and_return!(bot, msg.chat.id, "not_an_admin");
         }
 
-        // get request
-        let res: Result<RequestChatWithChat, sqlx::Error> = sqlx::query_as(
-            r#"SELECT "request_chat".id AS request_id, "request_chat".message, "chat".*
-            FROM "request_chat"
-            INNER JOIN "chat" ON "request_chat".requested_for = "chat".id
-            WHERE "request_chat".id = $1 AND "request_chat".is_approved = false
-            LIMIT 1;"#,
-        )
-        .bind(id)
-        .fetch_one(&db)
-        .await;
-        let request = match res {
-            Ok(request) => request,
-            Err(e) => match e {
-                sqlx::Error::RowNotFound => {
-                    bot.send_message(msg.chat.id, t!("chat_request_not_found"))
-                        .await?;
-                    return Ok(());
-                }
-                _ => return Err(Box::new(e)),
-            },
-        };
-
-        // approve request
-        sqlx::query(
-            r#"UPDATE "request_chat" SET approved_by = $1, is_approved = true WHERE id = $2;"#,
-        )
-        .bind(user.id)
-        .bind(request.request_id)
-        .execute(&db)
-        .await?;
-        event!(
-            Level::INFO,
-            "approved chat request {} by {} for {}",
-            request.request_id,
-            user,
-            request.chat
-        );
-        // notify target chat
-        bot.send_message(
-            Recipient::Id(ChatId(request.chat.tg_id)),
-            t!("chat_request_approved"),
-        )
-        .await?;
+        let reply = approve_chat_request(&bot, &db, &user, id).await?;
+        bot.send_message(msg.chat.id, t!(reply)).await?;
     }
 
     Ok(())
 }
 
-pub async fn cmd_decline_chat(bot: Bot, msg: Message, id: String, db: DbPool) -> HandlerResult {
+pub async fn cmd_decline_chat(bot: Bot, msg: Message, text: String, db: DbPool) -> HandlerResult {
+    let (id, reason) = split_reason(&text);
     let id: i32 = parse_integer!(bot, msg.chat.id, id);
 
     if let Some(user) = msg.from() {
@@ -167,50 +233,8 @@ pub async fn cmd_decline_chat(bot: Bot, msg: Message, id: String, db: DbPool) ->
             reply_i18n_and_return!(bot, msg.chat.id, "not_an_admin");
         }
 
-        // get request
-        let res: Result<RequestChatWithChat, sqlx::Error> = sqlx::query_as(
-            r#"SELECT "request_chat".id AS request_id, "request_chat".message, "chat".*
-            FROM "request_chat"
-            INNER JOIN "chat" ON "request_chat".requested_for = "chat".id
-            WHERE "request_chat".id = $1 AND "request_chat".is_approved = false
-            LIMIT 1;"#,
-        )
-        .bind(id)
-        .fetch_one(&db)
-        .await;
-        let request = match res {
-            Ok(request) => request,
-            Err(e) => match e {
-                sqlx::Error::RowNotFound => {
-                    bot.send_message(msg.chat.id, t!("chat_request_not_found"))
-                        .await?;
-                    return Ok(());
-                }
-                _ => return Err(Box::new(e)),
-            },
-        };
-
-        // decline request
-        sqlx::query(r#"DELETE FROM request_chat WHERE id = $1;"#)
-            .bind(request.request_id)
-            .execute(&db)
-            .await?;
-        event!(
-            Level::INFO,
-            "declined request {} by {} for {}",
-            request.request_id,
-            user,
-            request.chat
-        );
-        bot.send_message(msg.chat.id, t!("request_declined"))
-            .await?;
-
-        // notify target chat
-        bot.send_message(
-            Recipient::Id(ChatId(request.chat.tg_id)),
-            t!("chat_request_declined"),
-        )
-        .await?;
+        let reply = decline_chat_request(&bot, &db, &user, id, reason).await?;
+        bot.send_message(msg.chat.id, t!(reply)).await?;
     }
 
     Ok(())
@@ -0,0 +1,183 @@
+use rust_i18n::t;
+use teloxide::prelude::*;
+use tracing::{event, Level};
+
+use super::types::{HandlerErr, HandlerResult};
+use crate::db::request::revoke_requests;
+use crate::db::user::{
+    find_or_create_user, find_user_by_tg_id, find_user_by_username, list_users, set_user_banned,
+    set_user_can_download, UserFilter,
+};
+use crate::db::{DbPool, User};
+use crate::reply_i18n_and_return;
+
+const USERS_LIMIT: usize = 50;
+
+#[derive(Debug, PartialEq)]
+enum Target<'a> {
+    Username(&'a str),
+    TgId(i64),
+}
+
+// @username or tg id
+fn parse_target(text: &str) -> Option<Target> {
+    let text = text.trim();
+    if let Some(username) = text.strip_prefix('@') {
+        if !username.is_empty() && !username.contains(char::is_whitespace) {
+            return Some(Target::Username(username));
+        }
+        return None;
+    }
+
+    text.parse().ok().map(Target::TgId)
+}
+
+// checks that sender is admin and finds target user, which is author of replied
+// message or @username / tg id from command text. replies itself when either fails
+pub async fn admin_and_target(
+    bot: &Bot,
+    msg: &Message,
+    text: &str,
+    db: &DbPool,
+) -> Result<Option<(User, User)>, HandlerErr> {
+    let user = match msg.from() {
+        Some(user) => find_or_create_user(db, user).await?,
+        None => return Ok(None),
+    };
+    if !user.is_admin {
+        bot.send_message(msg.chat.id, t!("not_an_admin")).await?;
+        return Ok(None);
+    }
+
+    let target = if let Some(target) = msg.reply_to_message().and_then(|m| m.from()) {
+        Some(find_or_create_user(db, target).await?)
+    } else {
+        match parse_target(text) {
+            Some(Target::Username(username)) => find_user_by_username(db, username).await?,
+            Some(Target::TgId(tg_id)) => find_user_by_tg_id(db, tg_id).await?,
+            None => {
+                bot.send_message(msg.chat.id, t!("has_to_reply")).await?;
+                return Ok(None);
+            }
+        }
+    };
+
+    match target {
+        Some(target) => Ok(Some((user, target))),
+        None => {
+            bot.send_message(msg.chat.id, t!("user_not_found")).await?;
+            Ok(None)
+        }
+    }
+}
+
+pub async fn cmd_grant(bot: Bot, msg: Message, text: String, db: DbPool) -> HandlerResult {
+    if let Some((user, target)) = admin_and_target(&bot, &msg, &text, &db).await? {
+        set_user_can_download(&db, target.id, true).await?;
+        event!(Level::INFO, "granted download to {} by {}", target, user);
+        bot.send_message(msg.chat.id, t!("user_granted", user = target.to_string()))
+            .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn cmd_revoke(bot: Bot, msg: Message, text: String, db: DbPool) -> HandlerResult {
+    if let Some((user, target)) = admin_and_target(&bot, &msg, &text, &db).await? {
+        set_user_can_download(&db, target.id, false).await?;
+        revoke_requests(&db, target.id, user.id).await?;
+        event!(Level::INFO, "revoked download from {} by {}", target, user);
+        bot.send_message(msg.chat.id, t!("user_revoked", user = target.to_string()))
+            .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn cmd_ban(bot: Bot, msg: Message, text: String, db: DbPool) -> HandlerResult {
+    if let Some((user, target)) = admin_and_target(&bot, &msg, &text, &db).await? {
+        // admins have to be deopped first
+        if target.is_admin {
+            reply_i18n_and_return!(bot, msg.chat.id, "cant_do_that");
+        }
+
+        set_user_banned(&db, target.id, true).await?;
+        event!(Level::INFO, "banned {} by {}", target, user);
+        bot.send_message(msg.chat.id, t!("user_banned", user = target.to_string()))
+            .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn cmd_unban(bot: Bot, msg: Message, text: String, db: DbPool) -> HandlerResult {
+    if let Some((user, target)) = admin_and_target(&bot, &msg, &text, &db).await? {
+        set_user_banned(&db, target.id, false).await?;
+        event!(Level::INFO, "unbanned {} by {}", target, user);
+        bot.send_message(msg.chat.id, t!("user_unbanned", user = target.to_string()))
+            .await?;
+    }
+
+    Ok(())
+}
+
+fn parse_filter(text: &str) -> UserFilter {
+    match text.trim() {
+        "" => UserFilter::All,
+        "admins" => UserFilter::Admins,
+        "download" => UserFilter::CanDownload,
+        "banned" => UserFilter::Banned,
+        text => UserFilter::Search(text),
+    }
+}
+
+fn user_line(user: &User) -> String {
+    let mut line = user.to_string();
+    if user.is_admin {
+        line.push_str(" admin");
+    }
+    if user.can_download {
+        line.push_str(" download");
+    }
+    if user.is_banned {
+        line.push_str(" banned");
+    }
+
+    line
+}
+
+// /users [admins|download|banned|name]
+pub async fn cmd_users(bot: Bot, msg: Message, text: String, db: DbPool) -> HandlerResult {
+    if let Some(user) = msg.from() {
+        let user = find_or_create_user(&db, user).await?;
+        if !user.is_admin {
+            reply_i18n_and_return!(bot, msg.chat.id, "not_an_admin");
+        }
+
+        let users = list_users(&db, parse_filter(&text)).await?;
+
+        let mut list = String::new();
+        list.push_str(&t!("user_list_header", count = users.len().to_string()));
+        for user in users.iter().take(USERS_LIMIT) {
+            list.push_str(format!("{}\n", user_line(user)).as_str());
+        }
+        bot.send_message(msg.chat.id, list).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_target, Target};
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(parse_target("@someone"), Some(Target::Username("someone")));
+        assert_eq!(parse_target(" 12345 "), Some(Target::TgId(12345)));
+        assert_eq!(parse_target(""), None);
+        assert_eq!(parse_target("@"), None);
+        assert_eq!(parse_target("@some one"), None);
+        assert_eq!(parse_target("someone"), None);
+    }
+}
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use sqlx::migrate::MigrateDatabase;
 use sqlx::{PgPool, Postgres};
 use std::fmt;
@@ -6,6 +7,14 @@ use super::util::unwrap_env;
 
 pub type DbPool = PgPool;
 
+// quota overrides of user or chat, NULL means global one
+#[derive(sqlx::FromRow, Debug, Default, Clone, Copy)]
+pub struct QuotaOverride {
+    pub quota_hour: Option<i32>,
+    pub quota_day: Option<i32>,
+    pub quota_bytes: Option<i64>,
+}
+
 #[derive(sqlx::FromRow, Debug)]
 pub struct User {
     pub id: i32,
@@ -16,6 +25,9 @@ pub struct User {
     pub can_download: bool,
     pub is_admin: bool,
     pub has_private_chat: bool,
+    pub is_banned: bool,
+    #[sqlx(flatten)]
+    pub quota: QuotaOverride,
 }
 
 impl fmt::Display for User {
@@ -39,6 +51,15 @@ pub struct Chat {
     pub username: Option<String>,
     pub title: String,
     pub can_download: bool,
+    pub max_height: Option<i32>,
+    pub containers: Option<String>,
+    pub vcodecs: Option<String>,
+    pub acodecs: Option<String>,
+    pub max_vbr: Option<f32>,
+    pub max_fps: Option<f32>,
+    pub delete_link: bool,
+    #[sqlx(flatten)]
+    pub quota: QuotaOverride,
 }
 
 impl fmt::Display for Chat {
@@ -64,13 +85,65 @@ pub struct Link {
     pub auto_download: bool,
 }
 
+impl fmt::Display for Link {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} - {}{} download {} auto {}",
+            self.id,
+            self.domain,
+            self.path.as_deref().unwrap_or(""),
+            self.download_allowed,
+            self.auto_download
+        )
+    }
+}
+
+pub mod link;
+
+pub mod cache;
+
+#[derive(sqlx::FromRow, Debug)]
+pub struct Download {
+    pub id: i32,
+    pub user_id: Option<i32>,
+    pub chat_tg_id: i64,
+    pub url: String,
+    pub extractor: Option<String>,
+    pub video_id: Option<String>,
+    pub format_ids: Option<String>,
+    pub bytes: Option<i64>,
+    pub elapsed_ms: i64,
+    pub status: String,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    // joined from user table
+    pub user_name: Option<String>,
+}
+
+pub mod download;
+
+#[derive(sqlx::FromRow, Debug)]
+pub struct Notification {
+    pub id: i32,
+    pub kind: String,
+    pub request_id: i32,
+    pub chat_tg_id: i64,
+    pub message_id: i32,
+}
+
+pub mod notification;
+
 #[derive(sqlx::FromRow, Debug)]
 pub struct Request {
     pub id: i32,
     pub requested_by: i32,
-    pub approved_by: Option<i32>,
+    pub decided_by: Option<i32>,
     pub message: String,
-    pub is_approved: bool,
+    pub status: String,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(sqlx::FromRow, Debug)]
@@ -78,11 +151,16 @@ pub struct RequestChat {
     pub id: i32,
     pub requested_by: i32,
     pub requested_for: i32,
-    pub approved_by: Option<i32>,
+    pub decided_by: Option<i32>,
     pub message: String,
-    pub is_approved: bool,
+    pub status: String,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
 }
 
+pub mod request;
+
 pub fn make_database_url() -> String {
     format!(
         "postgres://{}:{}@{}/{}",
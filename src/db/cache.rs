@@ -0,0 +1,54 @@
+use super::DbPool;
+
+// cache key, quality is id of formats that were downloaded
+pub struct CacheKey<'a> {
+    pub extractor: &'a str,
+    pub video_id: &'a str,
+    pub quality: String,
+}
+
+pub async fn find_cached(db: &DbPool, key: &CacheKey<'_>) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"SELECT file_id FROM "download_cache"
+        WHERE extractor = $1 AND video_id = $2 AND quality = $3 LIMIT 1;"#,
+    )
+    .bind(key.extractor)
+    .bind(key.video_id)
+    .bind(&key.quality)
+    .fetch_optional(db)
+    .await
+}
+
+pub async fn save_cached(
+    db: &DbPool,
+    key: &CacheKey<'_>,
+    file_id: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"INSERT INTO "download_cache" (extractor, video_id, quality, file_id)
+        VALUES ($1,$2,$3,$4)
+        ON CONFLICT (extractor, video_id, quality) DO UPDATE SET file_id = $4;"#,
+    )
+    .bind(key.extractor)
+    .bind(key.video_id)
+    .bind(&key.quality)
+    .bind(file_id)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn delete_cached(db: &DbPool, key: &CacheKey<'_>) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"DELETE FROM "download_cache"
+        WHERE extractor = $1 AND video_id = $2 AND quality = $3;"#,
+    )
+    .bind(key.extractor)
+    .bind(key.video_id)
+    .bind(&key.quality)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
@@ -31,3 +31,50 @@ pub async fn find_or_create_chat(db: &DbPool, chat: &types::Chat) -> Result<Chat
 
     unwrap_or_create!(db, chat, res, create_chat)
 }
+
+pub async fn update_chat_format(db: &DbPool, chat: &Chat) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"UPDATE "chat"
+        SET max_height = $2, containers = $3, vcodecs = $4, acodecs = $5, max_vbr = $6, max_fps = $7
+        WHERE id = $1;"#,
+    )
+    .bind(chat.id)
+    .bind(chat.max_height)
+    .bind(&chat.containers)
+    .bind(&chat.vcodecs)
+    .bind(&chat.acodecs)
+    .bind(chat.max_vbr)
+    .bind(chat.max_fps)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn update_chat_quota(db: &DbPool, chat: &Chat) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"UPDATE "chat" SET quota_hour = $2, quota_day = $3, quota_bytes = $4 WHERE id = $1;"#,
+    )
+    .bind(chat.id)
+    .bind(chat.quota.quota_hour)
+    .bind(chat.quota.quota_day)
+    .bind(chat.quota.quota_bytes)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn update_chat_delete_link(
+    db: &DbPool,
+    chat_id: i32,
+    delete_link: bool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(r#"UPDATE "chat" SET delete_link = $2 WHERE id = $1;"#)
+        .bind(chat_id)
+        .bind(delete_link)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use super::{DbPool, Download};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DownloadStatus {
+    Done,
+    Cached,
+    Failed,
+    Cancelled,
+}
+
+impl DownloadStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Done => "done",
+            Self::Cached => "cached",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+// filled in while job runs, so failed downloads get recorded with whatever is known
+#[derive(Debug, Default)]
+pub struct DownloadRecord {
+    pub user_id: Option<i32>,
+    pub chat_tg_id: i64,
+    pub url: String,
+    pub extractor: Option<String>,
+    pub video_id: Option<String>,
+    pub format_ids: Option<String>,
+    pub bytes: Option<i64>,
+    pub elapsed_ms: i64,
+}
+
+#[derive(sqlx::FromRow, Debug)]
+pub struct UserUsage {
+    pub user_name: String,
+    pub downloads: i64,
+    pub bytes: i64,
+}
+
+// successful downloads over some period, cached ones count too
+#[derive(sqlx::FromRow, Debug, Default, Clone, Copy)]
+pub struct Usage {
+    pub downloads: i64,
+    pub bytes: i64,
+}
+
+pub async fn create_download(
+    db: &DbPool,
+    record: &DownloadRecord,
+    status: DownloadStatus,
+    error: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"INSERT INTO "download"
+        (user_id, chat_tg_id, url, extractor, video_id, format_ids, bytes, elapsed_ms, status, error)
+        VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10);"#,
+    )
+    .bind(record.user_id)
+    .bind(record.chat_tg_id)
+    .bind(&record.url)
+    .bind(&record.extractor)
+    .bind(&record.video_id)
+    .bind(&record.format_ids)
+    .bind(record.bytes)
+    .bind(record.elapsed_ms)
+    .bind(status.as_str())
+    .bind(error)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+// latest downloads of user, or of everyone if user_id is None
+pub async fn list_downloads(
+    db: &DbPool,
+    user_id: Option<i32>,
+    limit: i64,
+) -> Result<Vec<Download>, sqlx::Error> {
+    sqlx::query_as(
+        r#"SELECT "download".*, COALESCE("user".username, "user".first_name) AS user_name
+        FROM "download" LEFT JOIN "user" ON "user".id = "download".user_id
+        WHERE $1::INTEGER IS NULL OR "download".user_id = $1
+        ORDER BY "download".id DESC LIMIT $2;"#,
+    )
+    .bind(user_id)
+    .bind(limit)
+    .fetch_all(db)
+    .await
+}
+
+// users by downloaded bytes over last days
+pub async fn top_users(db: &DbPool, days: i32, limit: i64) -> Result<Vec<UserUsage>, sqlx::Error> {
+    sqlx::query_as(
+        r#"SELECT COALESCE("user".username, "user".first_name) AS user_name,
+            COUNT(*) AS downloads, COALESCE(SUM("download".bytes), 0)::BIGINT AS bytes
+        FROM "download" JOIN "user" ON "user".id = "download".user_id
+        WHERE "download".created_at > now() - make_interval(days => $1)
+        GROUP BY "user".id
+        ORDER BY bytes DESC LIMIT $2;"#,
+    )
+    .bind(days)
+    .bind(limit)
+    .fetch_all(db)
+    .await
+}
+
+pub async fn user_usage(db: &DbPool, user_id: i32, hours: i32) -> Result<Usage, sqlx::Error> {
+    sqlx::query_as(
+        r#"SELECT COUNT(*) AS downloads, COALESCE(SUM(bytes), 0)::BIGINT AS bytes
+        FROM "download"
+        WHERE user_id = $1 AND status IN ('done', 'cached')
+            AND created_at > now() - make_interval(hours => $2);"#,
+    )
+    .bind(user_id)
+    .bind(hours)
+    .fetch_one(db)
+    .await
+}
+
+pub async fn chat_usage(db: &DbPool, chat_tg_id: i64, hours: i32) -> Result<Usage, sqlx::Error> {
+    sqlx::query_as(
+        r#"SELECT COUNT(*) AS downloads, COALESCE(SUM(bytes), 0)::BIGINT AS bytes
+        FROM "download"
+        WHERE chat_tg_id = $1 AND status IN ('done', 'cached')
+            AND created_at > now() - make_interval(hours => $2);"#,
+    )
+    .bind(chat_tg_id)
+    .bind(hours)
+    .fetch_one(db)
+    .await
+}
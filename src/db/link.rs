@@ -0,0 +1,154 @@
+use url::Url;
+
+use super::{DbPool, Link};
+
+// "m.youtube.com" -> ["m.youtube.com", "youtube.com", "com"]
+fn domain_candidates(host: &str) -> Vec<String> {
+    let mut domains = Vec::new();
+    let mut rest = host.trim_end_matches('.');
+    loop {
+        domains.push(rest.to_lowercase());
+        match rest.split_once('.') {
+            Some((_, parent)) => rest = parent,
+            None => break,
+        }
+    }
+
+    domains
+}
+
+impl Link {
+    pub fn matches_path(&self, path: &str) -> bool {
+        match &self.path {
+            // "/shorts" matches "/shorts" and "/shorts/id", but not "/shortsfoo"
+            Some(prefix) => match path.strip_prefix(prefix.trim_end_matches('/')) {
+                Some(rest) => rest.is_empty() || rest.starts_with('/'),
+                None => false,
+            },
+            None => true,
+        }
+    }
+
+    // more specific links win: longer domain, then longer path
+    fn specificity(&self) -> (usize, usize) {
+        (
+            self.domain.len(),
+            self.path.as_ref().map(|p| p.len()).unwrap_or(0),
+        )
+    }
+}
+
+pub async fn find_link(db: &DbPool, url: &Url) -> Result<Option<Link>, sqlx::Error> {
+    let host = match url.host_str() {
+        Some(host) => host,
+        None => return Ok(None),
+    };
+
+    let links: Vec<Link> = sqlx::query_as(r#"SELECT * FROM "link" WHERE domain = ANY($1);"#)
+        .bind(domain_candidates(host))
+        .fetch_all(db)
+        .await?;
+
+    Ok(links
+        .into_iter()
+        .filter(|link| link.matches_path(url.path()))
+        .max_by_key(|link| link.specificity()))
+}
+
+pub async fn create_link(
+    db: &DbPool,
+    domain: &str,
+    path: Option<&str>,
+    auto_download: bool,
+) -> Result<Link, sqlx::Error> {
+    let link: Link = sqlx::query_as(
+        r#"INSERT INTO "link" (domain,path,download_allowed,auto_download)
+        VALUES ($1,$2,$3,$4)
+        RETURNING *;"#,
+    )
+    .bind(domain.to_lowercase())
+    .bind(path)
+    .bind(true)
+    .bind(auto_download)
+    .fetch_one(db)
+    .await?;
+
+    Ok(link)
+}
+
+pub async fn delete_link(db: &DbPool, id: i32) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(r#"DELETE FROM "link" WHERE id = $1;"#)
+        .bind(id)
+        .execute(db)
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn list_links(db: &DbPool) -> Result<Vec<Link>, sqlx::Error> {
+    sqlx::query_as(r#"SELECT * FROM "link" ORDER BY domain, path;"#)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn set_link_auto_download(
+    db: &DbPool,
+    id: i32,
+    auto_download: bool,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(r#"UPDATE "link" SET auto_download = $1 WHERE id = $2;"#)
+        .bind(auto_download)
+        .bind(id)
+        .execute(db)
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn set_link_download_allowed(
+    db: &DbPool,
+    id: i32,
+    download_allowed: bool,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(r#"UPDATE "link" SET download_allowed = $1 WHERE id = $2;"#)
+        .bind(download_allowed)
+        .bind(id)
+        .execute(db)
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::domain_candidates;
+    use crate::db::Link;
+
+    #[test]
+    fn test_domain_candidates() {
+        assert_eq!(
+            domain_candidates("m.YouTube.com"),
+            vec!["m.youtube.com", "youtube.com", "com"]
+        );
+        assert_eq!(domain_candidates("youtu.be."), vec!["youtu.be", "be"]);
+        assert_eq!(domain_candidates("localhost"), vec!["localhost"]);
+    }
+
+    #[test]
+    fn test_matches_path() {
+        let link = |path: Option<&str>| Link {
+            id: 1,
+            domain: "youtube.com".to_string(),
+            path: path.map(str::to_string),
+            download_allowed: true,
+            auto_download: false,
+        };
+        assert!(link(None).matches_path("/watch"));
+        assert!(link(Some("/shorts")).matches_path("/shorts"));
+        assert!(link(Some("/shorts")).matches_path("/shorts/abc"));
+        assert!(!link(Some("/shorts")).matches_path("/shortsfoo"));
+        assert!(!link(Some("/shorts")).matches_path("/watch"));
+        assert!(link(Some("/shorts/")).matches_path("/shorts/abc"));
+        assert!(link(Some("/")).matches_path("/watch"));
+    }
+}
@@ -0,0 +1,35 @@
+use super::{DbPool, Notification};
+
+pub async fn create_notification(
+    db: &DbPool,
+    kind: &str,
+    request_id: i32,
+    chat_tg_id: i64,
+    message_id: i32,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"INSERT INTO "notification" (kind, request_id, chat_tg_id, message_id)
+        VALUES ($1,$2,$3,$4);"#,
+    )
+    .bind(kind)
+    .bind(request_id)
+    .bind(chat_tg_id)
+    .bind(message_id)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+// removes notifications of request and returns them, so each one gets updated once
+pub async fn take_notifications(
+    db: &DbPool,
+    kind: &str,
+    request_id: i32,
+) -> Result<Vec<Notification>, sqlx::Error> {
+    sqlx::query_as(r#"DELETE FROM "notification" WHERE kind = $1 AND request_id = $2 RETURNING *;"#)
+        .bind(kind)
+        .bind(request_id)
+        .fetch_all(db)
+        .await
+}
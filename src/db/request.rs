@@ -0,0 +1,138 @@
+use chrono::{DateTime, Utc};
+
+use super::DbPool;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RequestStatus {
+    Pending,
+    Approved,
+    Declined,
+    Revoked,
+}
+
+impl RequestStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Approved => "approved",
+            Self::Declined => "declined",
+            Self::Revoked => "revoked",
+        }
+    }
+}
+
+pub async fn has_pending_request(db: &DbPool, user_id: i32) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"SELECT EXISTS(SELECT 1 FROM "request" WHERE requested_by = $1 AND status = $2);"#,
+    )
+    .bind(user_id)
+    .bind(RequestStatus::Pending.as_str())
+    .fetch_one(db)
+    .await
+}
+
+pub async fn has_pending_chat_request(db: &DbPool, chat_id: i32) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"SELECT EXISTS(
+            SELECT 1 FROM "request_chat" WHERE requested_for = $1 AND status = $2
+        );"#,
+    )
+    .bind(chat_id)
+    .bind(RequestStatus::Pending.as_str())
+    .fetch_one(db)
+    .await
+}
+
+// when the latest declined request of user was declined, for re-request cooldown
+pub async fn last_declined_at(
+    db: &DbPool,
+    user_id: i32,
+) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"SELECT MAX(decided_at) FROM "request"
+        WHERE requested_by = $1 AND status = $2;"#,
+    )
+    .bind(user_id)
+    .bind(RequestStatus::Declined.as_str())
+    .fetch_one(db)
+    .await
+}
+
+pub async fn last_chat_declined_at(
+    db: &DbPool,
+    chat_id: i32,
+) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"SELECT MAX(decided_at) FROM "request_chat"
+        WHERE requested_for = $1 AND status = $2;"#,
+    )
+    .bind(chat_id)
+    .bind(RequestStatus::Declined.as_str())
+    .fetch_one(db)
+    .await
+}
+
+// moves pending request to decided status, false if it isn't pending anymore
+pub async fn decide_request(
+    db: &DbPool,
+    id: i32,
+    status: RequestStatus,
+    decided_by: i32,
+    reason: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(
+        r#"UPDATE "request" SET status = $1, decided_by = $2, decided_at = now(), reason = $3
+        WHERE id = $4 AND status = $5;"#,
+    )
+    .bind(status.as_str())
+    .bind(decided_by)
+    .bind(reason)
+    .bind(id)
+    .bind(RequestStatus::Pending.as_str())
+    .execute(db)
+    .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn decide_chat_request(
+    db: &DbPool,
+    id: i32,
+    status: RequestStatus,
+    decided_by: i32,
+    reason: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(
+        r#"UPDATE "request_chat" SET status = $1, decided_by = $2, decided_at = now(), reason = $3
+        WHERE id = $4 AND status = $5;"#,
+    )
+    .bind(status.as_str())
+    .bind(decided_by)
+    .bind(reason)
+    .bind(id)
+    .bind(RequestStatus::Pending.as_str())
+    .execute(db)
+    .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+// marks approved requests of user as revoked, when download permission is taken away
+pub async fn revoke_requests(
+    db: &DbPool,
+    user_id: i32,
+    decided_by: i32,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"UPDATE "request" SET status = $1, decided_by = $2, decided_at = now()
+        WHERE requested_by = $3 AND status = $4;"#,
+    )
+    .bind(RequestStatus::Revoked.as_str())
+    .bind(decided_by)
+    .bind(user_id)
+    .bind(RequestStatus::Approved.as_str())
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
@@ -35,3 +35,97 @@ pub async fn find_or_create_user(db: &DbPool, user: &types::User) -> Result<User
 
     unwrap_or_create!(db, user, res, create_user)
 }
+
+pub async fn update_user_quota(db: &DbPool, user: &User) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"UPDATE "user" SET quota_hour = $2, quota_day = $3, quota_bytes = $4 WHERE id = $1;"#,
+    )
+    .bind(user.id)
+    .bind(user.quota.quota_hour)
+    .bind(user.quota.quota_day)
+    .bind(user.quota.quota_bytes)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn find_user_by_tg_id(db: &DbPool, tg_id: i64) -> Result<Option<User>, sqlx::Error> {
+    sqlx::query_as(r#"SELECT * FROM "user" WHERE tg_id = $1 LIMIT 1;"#)
+        .bind(tg_id)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn find_user_by_username(
+    db: &DbPool,
+    username: &str,
+) -> Result<Option<User>, sqlx::Error> {
+    sqlx::query_as(r#"SELECT * FROM "user" WHERE LOWER(username) = LOWER($1) LIMIT 1;"#)
+        .bind(username)
+        .fetch_optional(db)
+        .await
+}
+
+pub enum UserFilter<'a> {
+    All,
+    Admins,
+    CanDownload,
+    Banned,
+    // part of username or name
+    Search(&'a str),
+}
+
+pub async fn list_users(db: &DbPool, filter: UserFilter<'_>) -> Result<Vec<User>, sqlx::Error> {
+    let (condition, search) = match filter {
+        UserFilter::All => ("true", None),
+        UserFilter::Admins => ("is_admin", None),
+        UserFilter::CanDownload => ("can_download", None),
+        UserFilter::Banned => ("is_banned", None),
+        UserFilter::Search(text) => (
+            "username ILIKE $1 OR first_name ILIKE $1 OR last_name ILIKE $1",
+            Some(format!("%{}%", text)),
+        ),
+    };
+
+    let sql = format!(r#"SELECT * FROM "user" WHERE {} ORDER BY id;"#, condition);
+    let mut query = sqlx::query_as(&sql);
+    if let Some(search) = search {
+        query = query.bind(search);
+    }
+    query.fetch_all(db).await
+}
+
+pub async fn set_user_admin(db: &DbPool, id: i32, is_admin: bool) -> Result<(), sqlx::Error> {
+    sqlx::query(r#"UPDATE "user" SET is_admin = $1 WHERE id = $2;"#)
+        .bind(is_admin)
+        .bind(id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_user_can_download(
+    db: &DbPool,
+    id: i32,
+    can_download: bool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(r#"UPDATE "user" SET can_download = $1 WHERE id = $2;"#)
+        .bind(can_download)
+        .bind(id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_user_banned(db: &DbPool, id: i32, is_banned: bool) -> Result<(), sqlx::Error> {
+    sqlx::query(r#"UPDATE "user" SET is_banned = $1 WHERE id = $2;"#)
+        .bind(is_banned)
+        .bind(id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
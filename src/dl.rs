@@ -2,30 +2,60 @@ use std::fmt;
 use std::fs;
 use tracing::{event, Level};
 
-use crate::dl::ffmpeg::FFMpeg;
+use crate::dl::ffmpeg::{AudioTags, FFMpeg};
 
+use self::cancel::CancelToken;
+use self::clip::Clip;
+use self::limits::Limits;
+use self::policy::FormatPolicy;
+use self::progress::{Progress, ProgressSender};
 use self::spawn::SpawnError;
-use self::yt_dlp::{YtDlp, YtDlpError, YtDlpFormat, YtDlpInfo};
+use self::upload::fit_upload;
+use self::workspace::Workspace;
+use self::yt_dlp::{DownloadPart, VideoQuality, YtDlp, YtDlpError, YtDlpFormat, YtDlpInfo};
 
+mod args;
+pub mod cancel;
+pub mod clip;
 pub mod ffmpeg;
+pub mod limits;
+pub mod policy;
+pub mod progress;
 mod spawn;
+mod upload;
+pub mod workspace;
 pub mod yt_dlp;
 
 pub enum DownloadError {
     Message(String),
     NoFormatFound,
     MakePathError,
+    Cancelled,
+    Timeout,
+    TooLong,
+    TooLarge,
+    TooManyItems,
+    ClipOutOfRange,
+    Playlist,
 }
 
 impl From<SpawnError> for DownloadError {
     fn from(value: SpawnError) -> Self {
-        Self::Message(value.to_string())
+        match value {
+            SpawnError::Cancelled => Self::Cancelled,
+            SpawnError::Timeout => Self::Timeout,
+            _ => Self::Message(value.to_string()),
+        }
     }
 }
 
 impl From<YtDlpError> for DownloadError {
     fn from(value: YtDlpError) -> Self {
-        Self::Message(value.to_string())
+        match value {
+            YtDlpError::SpawnError(e) => e.into(),
+            YtDlpError::Timeout => Self::Timeout,
+            _ => Self::Message(value.to_string()),
+        }
     }
 }
 
@@ -39,27 +69,67 @@ impl fmt::Display for DownloadError {
                 "no best format found. you may want to specify one yourself"
             ),
             DE::MakePathError => write!(f, "failed to make path for download file"),
+            DE::Cancelled => write!(f, "download cancelled"),
+            DE::Timeout => write!(f, "download timed out"),
+            DE::TooLong => write!(f, "video is too long"),
+            DE::TooLarge => write!(f, "video is too large"),
+            DE::TooManyItems => write!(f, "too many items in gallery"),
+            DE::ClipOutOfRange => write!(f, "clip is out of video range"),
+            DE::Playlist => write!(f, "link is a playlist"),
         }
     }
 }
 
-fn make_download_path(
+// per-job state of the download pipeline
+pub struct DownloadContext {
+    pub progress: ProgressSender,
+    pub cancel: CancelToken,
+    pub limits: Limits,
+    pub policy: FormatPolicy,
+    pub workspace: Workspace,
+    pub clip: Option<Clip>,
+}
+
+impl DownloadContext {
+    // length of the result, only part of the video for clips
+    pub fn duration(&self, info: &YtDlpInfo) -> Option<f32> {
+        match self.clip {
+            Some(clip) => clip.duration(info.duration),
+            None => info.duration,
+        }
+    }
+}
+
+fn download_part(info: &YtDlpInfo, ctx: &DownloadContext) -> DownloadPart {
+    DownloadPart {
+        item: info.playlist_index,
+        clip: ctx.clip,
+    }
+}
+
+fn make_file_path(
+    ctx: &DownloadContext,
     info: &YtDlpInfo,
     suffix: Option<&str>,
-    format: &YtDlpFormat,
+    ext: &str,
 ) -> Result<String, DownloadError> {
-    std::env::temp_dir()
-        .join(format!(
-            "{}_{}.{}",
-            info.id,
-            suffix.unwrap_or(""),
-            format.ext
-        ))
+    ctx.workspace
+        .dir()
+        .join(format!("{}_{}.{}", info.id, suffix.unwrap_or(""), ext))
         .into_os_string()
         .into_string()
         .map_err(|e| DownloadError::MakePathError)
 }
 
+fn make_download_path(
+    ctx: &DownloadContext,
+    info: &YtDlpInfo,
+    suffix: Option<&str>,
+    format: &YtDlpFormat,
+) -> Result<String, DownloadError> {
+    make_file_path(ctx, info, suffix, &format.ext)
+}
+
 fn file_exists(path: &str) -> bool {
     match fs::metadata(path) {
         Ok(_) => true,
@@ -67,7 +137,7 @@ fn file_exists(path: &str) -> bool {
     }
 }
 
-pub fn delete_if_exists(path: &str) {
+fn delete_if_exists(path: &str) {
     if file_exists(path) {
         if let Err(e) = fs::remove_file(path) {
             event!(Level::ERROR, "{}", e);
@@ -75,61 +145,217 @@ pub fn delete_if_exists(path: &str) {
     }
 }
 
-async fn download_fallback(url: &str, info: YtDlpInfo) -> Result<String, DownloadError> {
+// formats to download, separate video and audio get merged after
+pub enum FormatSelection<'a> {
+    Merge {
+        video: &'a YtDlpFormat,
+        audio: &'a YtDlpFormat,
+    },
+    Single(&'a YtDlpFormat),
+}
+
+impl FormatSelection<'_> {
+    fn video(&self) -> &YtDlpFormat {
+        match self {
+            Self::Merge { video, .. } => video,
+            Self::Single(format) => format,
+        }
+    }
+
+    pub fn width(&self) -> Option<u16> {
+        self.video().width
+    }
+
+    pub fn height(&self) -> Option<u16> {
+        self.video().height
+    }
+
+    // same formats give the same file, so it identifies download result
+    pub fn id(&self) -> String {
+        match self {
+            Self::Merge { video, audio } => format!("{}+{}", video.format_id, audio.format_id),
+            Self::Single(format) => format.format_id.clone(),
+        }
+    }
+}
+
+pub async fn load_info(url: &str, ctx: &DownloadContext) -> Result<YtDlpInfo, DownloadError> {
+    event!(Level::INFO, "url {}", url);
+
+    ctx.progress.send_replace(Progress::FetchingInfo);
+    let info = YtDlp::load_info(
+        url,
+        ctx.limits.max_gallery_items,
+        &ctx.cancel,
+        ctx.limits.info_timeout,
+    )
+    .await?;
+    if info.is_playlist() {
+        return Err(DownloadError::Playlist);
+    }
+    if ctx.clip.is_some_and(|clip| !clip.fits(info.duration)) {
+        return Err(DownloadError::ClipOutOfRange);
+    }
+    // long videos are fine as long as the clip is short
+    if !ctx.limits.check_duration(ctx.duration(&info)) {
+        return Err(DownloadError::TooLong);
+    }
+    if info.entries.len() > ctx.limits.max_gallery_items {
+        return Err(DownloadError::TooManyItems);
+    }
+
+    Ok(info)
+}
+
+fn select_fallback<'a>(
+    info: &'a YtDlpInfo,
+    ctx: &DownloadContext,
+) -> Result<FormatSelection<'a>, DownloadError> {
     let av = match info.best_av_format() {
         Some(av) => av,
         None => {
             event!(
                 Level::WARN,
                 "no best format found for {}, reverting to default",
-                url
+                info.id
             );
-            match info.default_format() {
+            match info.default_format(&ctx.policy) {
                 Some(format) => format,
                 None => {
-                    event!(Level::ERROR, "no formats found for {}", url);
+                    event!(Level::ERROR, "no formats found for {}", info.id);
                     return Err(DownloadError::NoFormatFound);
                 }
             }
         }
     };
 
-    let output_path = make_download_path(&info, None, &av)?;
-    if let Err(e) = YtDlp::download(url, &av.format_id, output_path.as_str()).await {
-        delete_if_exists(&output_path);
-        return Err(DownloadError::Message(e.to_string()));
+    if !ctx.limits.check_filesize(av.approx_filesize()) {
+        return Err(DownloadError::TooLarge);
     }
 
-    Ok(output_path)
+    Ok(FormatSelection::Single(av))
 }
 
-pub async fn download(url: &str) -> Result<String, DownloadError> {
-    event!(Level::INFO, "url {}", url);
-
-    let info = YtDlp::load_info(url).await?;
-    let vf = match info.best_video_format() {
-        Some(vf) => vf,
-        None => return download_fallback(url, info).await,
+pub fn select_formats<'a>(
+    info: &'a YtDlpInfo,
+    quality: Option<&VideoQuality>,
+    ctx: &DownloadContext,
+) -> Result<FormatSelection<'a>, DownloadError> {
+    // quality picked by user is taken as is, no fallback
+    let vf = match quality {
+        Some(quality) => match info.video_format_for(quality) {
+            Some(vf) => vf,
+            None => return Err(DownloadError::NoFormatFound),
+        },
+        None => match info.best_video_format(&ctx.policy) {
+            Some(vf) => vf,
+            None => return select_fallback(info, ctx),
+        },
     };
-    let af = match info.best_audio_format() {
+    let af = match info.best_audio_format(&ctx.policy) {
         Some(af) => af,
-        None => return download_fallback(url, info).await,
+        None => return select_fallback(info, ctx),
     };
 
-    // TODO: I should wrap those temp files in a impl Drop for defer deletion
-    let video_path = make_download_path(&info, Some("video"), &vf)?;
-    if let Err(e) = YtDlp::download(url, &vf.format_id, video_path.as_str()).await {
-        delete_if_exists(&video_path);
-        return Err(DownloadError::Message(e.to_string()));
-    }
+    // rather take lower quality than re-encode or split it later
+    let vf = match (vf.approx_filesize(), af.approx_filesize()) {
+        (Some(video), Some(audio))
+            if quality.is_none() && video + audio > ctx.limits.max_upload_size =>
+        {
+            let max_size = ctx.limits.max_upload_size.saturating_sub(audio);
+            match info.best_video_format_within(&ctx.policy, max_size) {
+                Some(smaller) => {
+                    event!(
+                        Level::INFO,
+                        "for {} picked {} instead of {} to fit upload limit",
+                        info.id,
+                        smaller.format_id,
+                        vf.format_id
+                    );
+                    smaller
+                }
+                None => vf,
+            }
+        }
+        _ => vf,
+    };
 
-    let audio_path = make_download_path(&info, Some("audio"), &af)?;
-    if let Err(e) = YtDlp::download(url, &af.format_id, audio_path.as_str()).await {
-        delete_if_exists(&video_path);
-        delete_if_exists(&audio_path);
-        return Err(DownloadError::Message(e.to_string()));
+    let filesize = vf
+        .approx_filesize()
+        .and_then(|video| Some(video + af.approx_filesize()?));
+    if !ctx.limits.check_filesize(filesize) {
+        return Err(DownloadError::TooLarge);
     }
 
+    Ok(FormatSelection::Merge {
+        video: vf,
+        audio: af,
+    })
+}
+
+async fn download_single(
+    url: &str,
+    info: &YtDlpInfo,
+    av: &YtDlpFormat,
+    ctx: &DownloadContext,
+) -> Result<Vec<String>, DownloadError> {
+    let output_path = make_download_path(ctx, info, None, av)?;
+    YtDlp::download(
+        url,
+        download_part(info, ctx),
+        &av.format_id,
+        output_path.as_str(),
+        &ctx.cancel,
+        ctx.limits.download_timeout,
+        |percent| {
+            ctx.progress.send_replace(Progress::Video(percent));
+        },
+    )
+    .await?;
+
+    fit_upload(ctx, output_path, ctx.duration(info)).await
+}
+
+// returns paths of files to upload, more than one if video had to be split
+pub async fn download(
+    url: &str,
+    info: &YtDlpInfo,
+    selection: &FormatSelection<'_>,
+    ctx: &DownloadContext,
+) -> Result<Vec<String>, DownloadError> {
+    let (vf, af) = match *selection {
+        FormatSelection::Merge { video, audio } => (video, audio),
+        FormatSelection::Single(av) => return download_single(url, info, av, ctx).await,
+    };
+
+    let video_path = make_download_path(ctx, info, Some("video"), vf)?;
+    YtDlp::download(
+        url,
+        download_part(info, ctx),
+        &vf.format_id,
+        video_path.as_str(),
+        &ctx.cancel,
+        ctx.limits.download_timeout,
+        |percent| {
+            ctx.progress.send_replace(Progress::Video(percent));
+        },
+    )
+    .await?;
+
+    let audio_path = make_download_path(ctx, info, Some("audio"), af)?;
+    YtDlp::download(
+        url,
+        download_part(info, ctx),
+        &af.format_id,
+        audio_path.as_str(),
+        &ctx.cancel,
+        ctx.limits.download_timeout,
+        |percent| {
+            ctx.progress.send_replace(Progress::Audio(percent));
+        },
+    )
+    .await?;
+
     let abr = if let Some(abr) = af.abr {
         FFMpeg::round_mp3_bitrate(abr)
     } else {
@@ -143,7 +369,8 @@ pub async fn download(url: &str) -> Result<String, DownloadError> {
         192
     };
 
-    let output_path = make_download_path(&info, None, &vf)?;
+    // mp4 takes any codec user could pick, so merged file is always mp4
+    let output_path = make_file_path(ctx, info, None, "mp4")?;
 
     event!(
         Level::INFO,
@@ -153,18 +380,264 @@ pub async fn download(url: &str) -> Result<String, DownloadError> {
         af.format_id
     );
 
-    let res = FFMpeg::join_video_audio(
+    ctx.progress.send_replace(Progress::Merging);
+    FFMpeg::join_video_audio(
         video_path.as_str(),
         audio_path.as_str(),
         abr,
         output_path.as_str(),
+        &ctx.cancel,
+        ctx.limits.merge_timeout,
     )
-    .await;
+    .await?;
+
+    // free up space early, since workspace lives until upload is done
     delete_if_exists(&video_path);
     delete_if_exists(&audio_path);
 
-    match res {
-        Ok(()) => Ok(output_path),
-        Err(e) => Err(DownloadError::Message(e.to_string())),
+    fit_upload(ctx, output_path, ctx.duration(info)).await
+}
+
+pub enum GalleryItem {
+    Photo(String),
+    Video(String),
+}
+
+pub struct GalleryDownload {
+    pub items: Vec<GalleryItem>,
+    // slideshows come with music, it can't go into an album with photos
+    pub audio: Option<AudioDownload>,
+}
+
+async fn download_image(
+    url: &str,
+    info: &YtDlpInfo,
+    format: &YtDlpFormat,
+    ctx: &DownloadContext,
+) -> Result<String, DownloadError> {
+    if !ctx.limits.check_filesize(format.approx_filesize()) {
+        return Err(DownloadError::TooLarge);
+    }
+
+    let output_path = make_download_path(ctx, info, Some("image"), format)?;
+    let part = DownloadPart {
+        item: info.playlist_index,
+        clip: None,
+    };
+    YtDlp::download(
+        url,
+        part,
+        &format.format_id,
+        output_path.as_str(),
+        &ctx.cancel,
+        ctx.limits.download_timeout,
+        |_| {},
+    )
+    .await?;
+
+    if fs::metadata(&output_path).is_ok_and(|m| m.len() > ctx.limits.max_upload_size) {
+        return Err(DownloadError::TooLarge);
+    }
+
+    Ok(output_path)
+}
+
+// downloads every entry of a gallery, in order
+pub async fn download_gallery(
+    url: &str,
+    info: &YtDlpInfo,
+    ctx: &DownloadContext,
+) -> Result<GalleryDownload, DownloadError> {
+    let entries = info.gallery_entries();
+    let total = entries.len();
+    let mut items = Vec::new();
+    let mut audio = None;
+    for (i, entry) in entries.into_iter().enumerate() {
+        ctx.progress.send_replace(Progress::Item(i + 1, total));
+        if let Some(image) = entry.best_image_format() {
+            let path = download_image(url, entry, image, ctx).await?;
+            items.push(GalleryItem::Photo(path));
+        } else if entry.has_video() {
+            if !ctx.limits.check_duration(entry.duration) {
+                return Err(DownloadError::TooLong);
+            }
+            let selection = select_formats(entry, None, ctx)?;
+            let parts = download(url, entry, &selection, ctx).await?;
+            items.extend(parts.into_iter().map(GalleryItem::Video));
+        } else if audio.is_none() {
+            let af = select_audio_format(entry, ctx)?;
+            audio = Some(download_audio(url, entry, af, ctx).await?);
+        }
+    }
+
+    if items.is_empty() {
+        event!(Level::ERROR, "no gallery items found for {}", info.id);
+        return Err(DownloadError::NoFormatFound);
+    }
+
+    Ok(GalleryDownload { items, audio })
+}
+
+// thumbnail is optional, video or audio without it is still fine
+async fn download_thumbnail(
+    url: &str,
+    info: &YtDlpInfo,
+    ctx: &DownloadContext,
+) -> Result<Option<String>, DownloadError> {
+    if info.thumbnail.is_none() {
+        return Ok(None);
+    }
+
+    let cover_path = make_file_path(ctx, info, Some("cover"), "jpg")?;
+    match YtDlp::download_thumbnail(
+        url,
+        info.playlist_index,
+        cover_path.as_str(),
+        &ctx.cancel,
+        ctx.limits.info_timeout,
+    )
+    .await
+    {
+        Ok(()) => Ok(Some(cover_path)),
+        Err(_) if ctx.cancel.is_cancelled() => Err(DownloadError::Cancelled),
+        Err(e) => {
+            event!(Level::WARN, "no thumbnail for {} - {}", url, e);
+            Ok(None)
+        }
+    }
+}
+
+// thumbnail small enough for telegram video preview
+pub async fn download_video_thumbnail(
+    url: &str,
+    info: &YtDlpInfo,
+    ctx: &DownloadContext,
+) -> Result<Option<String>, DownloadError> {
+    let cover_path = match download_thumbnail(url, info, ctx).await? {
+        Some(cover_path) => cover_path,
+        None => return Ok(None),
+    };
+
+    let thumb_path = make_file_path(ctx, info, Some("thumb"), "jpg")?;
+    match FFMpeg::make_thumbnail(
+        cover_path.as_str(),
+        thumb_path.as_str(),
+        &ctx.cancel,
+        ctx.limits.merge_timeout,
+    )
+    .await
+    {
+        Ok(()) => Ok(Some(thumb_path)),
+        Err(_) if ctx.cancel.is_cancelled() => Err(DownloadError::Cancelled),
+        Err(e) => {
+            event!(Level::WARN, "no video thumbnail for {} - {}", url, e);
+            Ok(None)
+        }
+    }
+}
+
+pub struct AudioDownload {
+    pub path: String,
+    pub title: String,
+    pub performer: Option<String>,
+    pub duration: Option<f32>,
+}
+
+pub fn select_audio_format<'a>(
+    info: &'a YtDlpInfo,
+    ctx: &DownloadContext,
+) -> Result<&'a YtDlpFormat, DownloadError> {
+    let af = match info.best_audio_format(&ctx.policy) {
+        Some(af) => af,
+        None => return Err(DownloadError::NoFormatFound),
+    };
+    if !ctx.limits.check_filesize(af.approx_filesize()) {
+        return Err(DownloadError::TooLarge);
+    }
+
+    Ok(af)
+}
+
+pub async fn download_audio(
+    url: &str,
+    info: &YtDlpInfo,
+    af: &YtDlpFormat,
+    ctx: &DownloadContext,
+) -> Result<AudioDownload, DownloadError> {
+    let audio_path = make_download_path(ctx, info, Some("audio"), af)?;
+    YtDlp::download(
+        url,
+        download_part(info, ctx),
+        &af.format_id,
+        audio_path.as_str(),
+        &ctx.cancel,
+        ctx.limits.download_timeout,
+        |percent| {
+            ctx.progress.send_replace(Progress::Audio(percent));
+        },
+    )
+    .await?;
+
+    let cover_path = download_thumbnail(url, info, ctx).await?;
+
+    let title = info.track.clone().unwrap_or_else(|| info.title.clone());
+    let performer = info.artist.clone().or_else(|| info.uploader.clone());
+    let tags = AudioTags {
+        title: title.as_str(),
+        artist: performer.as_deref(),
+    };
+
+    ctx.progress.send_replace(Progress::Converting);
+    // aac can go into m4a as is, everything else is converted to mp3
+    let is_aac = af.ext == "m4a"
+        && af
+            .acodec
+            .as_ref()
+            .is_some_and(|acodec| acodec.starts_with("mp4a"));
+    let output_path = if is_aac {
+        let output_path = make_download_path(ctx, info, None, af)?;
+        FFMpeg::tag_audio(
+            audio_path.as_str(),
+            output_path.as_str(),
+            &tags,
+            cover_path.as_deref(),
+            &ctx.cancel,
+            ctx.limits.merge_timeout,
+        )
+        .await?;
+
+        output_path
+    } else {
+        let bitrate = match af.abr {
+            Some(abr) => FFMpeg::round_mp3_bitrate(abr),
+            None => 192,
+        };
+        let output_path = make_file_path(ctx, info, None, "mp3")?;
+        FFMpeg::convert_to_mp3(
+            audio_path.as_str(),
+            output_path.as_str(),
+            bitrate,
+            &tags,
+            cover_path.as_deref(),
+            &ctx.cancel,
+            ctx.limits.merge_timeout,
+        )
+        .await?;
+
+        output_path
+    };
+
+    delete_if_exists(&audio_path);
+
+    // splitting audio would break tags and cover, so there is no fallback
+    if fs::metadata(&output_path).is_ok_and(|m| m.len() > ctx.limits.max_upload_size) {
+        return Err(DownloadError::TooLarge);
     }
+
+    Ok(AudioDownload {
+        path: output_path,
+        title,
+        performer,
+        duration: ctx.duration(info),
+    })
 }
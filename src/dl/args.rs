@@ -0,0 +1,165 @@
+use core::fmt;
+
+use crate::bot::sanitize::parse_url;
+
+const ALLOWED_SCHEMES: [&str; 2] = ["http", "https"];
+
+#[derive(Debug, PartialEq)]
+pub enum ArgError {
+    OptionLike(String),
+    InvalidUrl(String),
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ArgError as AE;
+        match self {
+            AE::OptionLike(value) => write!(f, "argument looks like an option - {}", value),
+            AE::InvalidUrl(url) => write!(f, "invalid url - {}", url),
+        }
+    }
+}
+
+/* Argument list builder for yt-dlp and ffmpeg. Option names can only be
+static strings, so anything coming from user has to go through validated
+values or positional inputs, which are placed after "--" */
+#[derive(Debug, Default)]
+pub struct Args {
+    args: Vec<String>,
+    output: Option<String>,
+    positional: Vec<String>,
+}
+
+impl Args {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn validate_value(value: &str) -> Result<(), ArgError> {
+        if value.is_empty() || value.starts_with('-') {
+            Err(ArgError::OptionLike(value.to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn validate_url(url: &str) -> Result<String, ArgError> {
+        if url.starts_with('-') {
+            return Err(ArgError::OptionLike(url.to_string()));
+        }
+
+        match parse_url(url) {
+            Some(parsed)
+                if ALLOWED_SCHEMES.contains(&parsed.scheme()) && parsed.host_str().is_some() =>
+            {
+                Ok(parsed.to_string())
+            }
+            _ => Err(ArgError::InvalidUrl(url.to_string())),
+        }
+    }
+
+    pub fn flag(mut self, flag: &'static str) -> Self {
+        self.args.push(flag.to_string());
+        self
+    }
+
+    pub fn opt(mut self, name: &'static str, value: &str) -> Result<Self, ArgError> {
+        Self::validate_value(value)?;
+        self.args.push(name.to_string());
+        self.args.push(value.to_string());
+        Ok(self)
+    }
+
+    pub fn url(mut self, url: &str) -> Result<Self, ArgError> {
+        self.positional.push(Self::validate_url(url)?);
+        Ok(self)
+    }
+
+    // ffmpeg doesn't know about "--", so its output file goes last without separator
+    pub fn output(mut self, path: &str) -> Result<Self, ArgError> {
+        Self::validate_value(path)?;
+        self.output = Some(path.to_string());
+        Ok(self)
+    }
+
+    pub fn build(self) -> Vec<String> {
+        let mut args = self.args;
+        if let Some(output) = self.output {
+            args.push(output);
+        }
+        if !self.positional.is_empty() {
+            args.push("--".to_string());
+            args.extend(self.positional);
+        }
+
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArgError, Args};
+
+    #[test]
+    fn test_url_goes_after_separator() {
+        let args = Args::new()
+            .opt("-f", "137")
+            .unwrap()
+            .url("https://www.youtube.com/watch?v=00000000000")
+            .unwrap()
+            .build();
+        assert_eq!(
+            args,
+            vec![
+                "-f",
+                "137",
+                "--",
+                "https://www.youtube.com/watch?v=00000000000"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reject_option_like_url() {
+        assert_eq!(
+            Args::new().url("--exec").unwrap_err(),
+            ArgError::OptionLike("--exec".to_string())
+        );
+        assert_eq!(
+            Args::new().url("-o /etc/passwd").unwrap_err(),
+            ArgError::OptionLike("-o /etc/passwd".to_string())
+        );
+        assert!(Args::new()
+            .url("--exec=rm -rf ~ https://youtu.be/0")
+            .is_err());
+    }
+
+    #[test]
+    fn test_reject_bad_scheme() {
+        assert!(Args::new().url("file:///etc/passwd").is_err());
+        assert!(Args::new().url("javascript:alert(1)").is_err());
+        assert!(Args::new().url("ftp://example.com/video.mp4").is_err());
+        assert!(Args::new().url("youtube.com/watch?v=00000000000").is_err());
+    }
+
+    #[test]
+    fn test_reject_option_like_value() {
+        assert!(Args::new().opt("-f", "--exec").is_err());
+        assert!(Args::new().opt("-o", "-o /etc/passwd").is_err());
+        assert!(Args::new().opt("-f", "").is_err());
+        assert!(Args::new().output("-y").is_err());
+        assert!(Args::new().output("/tmp/video.mp4").is_ok());
+    }
+
+    #[test]
+    fn test_output_goes_last() {
+        let args = Args::new()
+            .opt("-i", "/tmp/video.mp4")
+            .unwrap()
+            .flag("-y")
+            .output("/tmp/out.mp4")
+            .unwrap()
+            .build();
+        assert_eq!(args, vec!["-i", "/tmp/video.mp4", "-y", "/tmp/out.mp4"]);
+    }
+}
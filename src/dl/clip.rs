@@ -0,0 +1,78 @@
+use std::fmt;
+
+// part of video to download, in seconds. open end means until the end of video
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clip {
+    pub start: u32,
+    pub end: Option<u32>,
+}
+
+impl Clip {
+    // start has to be inside the video, end can't go past it
+    pub fn fits(&self, duration: Option<f32>) -> bool {
+        let duration = match duration {
+            Some(duration) => duration,
+            None => return true,
+        };
+        if self.start as f32 >= duration {
+            return false;
+        }
+        match self.end {
+            Some(end) => end as f32 <= duration.ceil(),
+            None => true,
+        }
+    }
+
+    pub fn duration(&self, duration: Option<f32>) -> Option<f32> {
+        match self.end {
+            Some(end) => Some((end - self.start) as f32),
+            None => duration.map(|duration| duration - self.start as f32),
+        }
+    }
+
+    // yt-dlp --download-sections value, * marks time range instead of chapter name
+    pub fn section(&self) -> String {
+        match self.end {
+            Some(end) => format!("*{}-{}", self.start, end),
+            None => format!("*{}-inf", self.start),
+        }
+    }
+}
+
+impl fmt::Display for Clip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.end {
+            Some(end) => write!(f, "{}-{}", self.start, end),
+            None => write!(f, "{}-", self.start),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Clip;
+
+    #[test]
+    fn test_clip() {
+        let clip = Clip {
+            start: 83,
+            end: Some(103),
+        };
+        assert!(clip.fits(Some(7200.0)));
+        assert!(clip.fits(Some(102.4)));
+        assert!(!clip.fits(Some(90.0)));
+        assert!(clip.fits(None));
+        assert_eq!(clip.duration(Some(7200.0)), Some(20.0));
+        assert_eq!(clip.section(), "*83-103");
+
+        let clip = Clip {
+            start: 7000,
+            end: None,
+        };
+        assert!(clip.fits(Some(7200.0)));
+        assert!(!clip.fits(Some(7000.0)));
+        assert_eq!(clip.duration(Some(7200.0)), Some(200.0));
+        assert_eq!(clip.duration(None), None);
+        assert_eq!(clip.section(), "*7000-inf");
+    }
+}
@@ -1,5 +1,14 @@
+use std::time::Duration;
+
+use super::args::{ArgError, Args};
+use super::cancel::CancelToken;
 use super::spawn::{spawn, SpawnError};
 
+pub struct AudioTags<'a> {
+    pub title: &'a str,
+    pub artist: Option<&'a str>,
+}
+
 pub struct FFMpeg {}
 
 impl FFMpeg {
@@ -14,26 +23,65 @@ impl FFMpeg {
             .unwrap_or(320)
     }
 
+    // audio input with optional cover picture and tags
+    fn audio_args(
+        input_path: &str,
+        cover_path: Option<&str>,
+        tags: &AudioTags<'_>,
+    ) -> Result<Args, ArgError> {
+        let mut args = Args::new().opt("-i", input_path)?;
+        if let Some(cover_path) = cover_path {
+            args = args
+                .opt("-i", cover_path)?
+                .opt("-map", "0:a:0")?
+                .opt("-map", "1:0")?
+                .opt("-c:v", "mjpeg")?
+                .opt("-disposition:v:0", "attached_pic")?;
+        }
+
+        args = args.opt("-metadata", format!("title={}", tags.title).as_str())?;
+        if let Some(artist) = tags.artist {
+            args = args.opt("-metadata", format!("artist={}", artist).as_str())?;
+        }
+
+        Ok(args)
+    }
+
     pub async fn convert_to_mp3(
         input_path: &str,
         output_path: &str,
         bitrate: u16,
+        tags: &AudioTags<'_>,
+        cover_path: Option<&str>,
+        cancel: &CancelToken,
+        timeout: Duration,
     ) -> Result<(), SpawnError> {
         let bitrate = format!("{}k", bitrate);
-        let output = spawn(
-            "ffmpeg",
-            &[
-                "-i",
-                input_path,
-                "-codec:a",
-                "libmp3lame",
-                "-b:a",
-                bitrate.as_str(),
-                "-y",
-                output_path,
-            ],
-        )
-        .await?;
+        let args = Self::audio_args(input_path, cover_path, tags)?
+            .opt("-codec:a", "libmp3lame")?
+            .opt("-b:a", bitrate.as_str())?
+            .opt("-id3v2_version", "3")?
+            .flag("-y")
+            .output(output_path)?;
+        spawn("ffmpeg", args, cancel, timeout).await?;
+
+        Ok(())
+    }
+
+    // keeps audio stream as is, only embeds tags and cover
+    pub async fn tag_audio(
+        input_path: &str,
+        output_path: &str,
+        tags: &AudioTags<'_>,
+        cover_path: Option<&str>,
+        cancel: &CancelToken,
+        timeout: Duration,
+    ) -> Result<(), SpawnError> {
+        let args = Self::audio_args(input_path, cover_path, tags)?
+            .opt("-c:a", "copy")?
+            .flag("-y")
+            .output(output_path)?;
+        spawn("ffmpeg", args, cancel, timeout).await?;
 
         Ok(())
     }
@@ -43,29 +91,90 @@ impl FFMpeg {
         audio_path: &str,
         abr: u16,
         output_path: &str,
+        cancel: &CancelToken,
+        timeout: Duration,
     ) -> Result<(), SpawnError> {
         let abr = format!("{}k", abr);
-        let output = spawn(
-            "ffmpeg",
-            &[
-                "-i",
-                video_path,
-                "-i",
-                audio_path,
-                "-c",
-                "copy",
-                "-map",
-                "0:v:0",
-                "-map",
-                "1:a:0",
-                "-c:a",
-                "aac",
-                "-b:a",
-                &abr,
-                output_path,
-            ],
-        )
-        .await?;
+        let args = Args::new()
+            .opt("-i", video_path)?
+            .opt("-i", audio_path)?
+            .opt("-c", "copy")?
+            .opt("-map", "0:v:0")?
+            .opt("-map", "1:a:0")?
+            .opt("-c:a", "aac")?
+            .opt("-b:a", &abr)?
+            .output(output_path)?;
+        let output = spawn("ffmpeg", args, cancel, timeout).await?;
+
+        Ok(())
+    }
+
+    // single pass with target bitrate, good enough to fit into upload limit
+    pub async fn reencode_video(
+        input_path: &str,
+        output_path: &str,
+        vbr: u32,
+        abr: u16,
+        cancel: &CancelToken,
+        timeout: Duration,
+    ) -> Result<(), SpawnError> {
+        let bufsize = format!("{}k", vbr * 2);
+        let vbr = format!("{}k", vbr);
+        let abr = format!("{}k", abr);
+        let args = Args::new()
+            .opt("-i", input_path)?
+            .opt("-c:v", "libx264")?
+            .opt("-preset", "veryfast")?
+            .opt("-b:v", &vbr)?
+            .opt("-maxrate", &vbr)?
+            .opt("-bufsize", &bufsize)?
+            .opt("-c:a", "aac")?
+            .opt("-b:a", &abr)?
+            .opt("-movflags", "+faststart")?
+            .flag("-y")
+            .output(output_path)?;
+        spawn("ffmpeg", args, cancel, timeout).await?;
+
+        Ok(())
+    }
+
+    // telegram ignores video thumbnails larger than 320px or 200KB
+    pub async fn make_thumbnail(
+        input_path: &str,
+        output_path: &str,
+        cancel: &CancelToken,
+        timeout: Duration,
+    ) -> Result<(), SpawnError> {
+        let args = Args::new()
+            .opt("-i", input_path)?
+            .opt("-vf", "scale=320:320:force_original_aspect_ratio=decrease")?
+            .opt("-q:v", "5")?
+            .flag("-y")
+            .output(output_path)?;
+        spawn("ffmpeg", args, cancel, timeout).await?;
+
+        Ok(())
+    }
+
+    // output_pattern should contain %03d for part number
+    pub async fn split(
+        input_path: &str,
+        output_pattern: &str,
+        segment_time: u32,
+        cancel: &CancelToken,
+        timeout: Duration,
+    ) -> Result<(), SpawnError> {
+        let segment_time = segment_time.to_string();
+        let args = Args::new()
+            .opt("-i", input_path)?
+            .opt("-map", "0")?
+            .opt("-c", "copy")?
+            .opt("-f", "segment")?
+            .opt("-segment_time", &segment_time)?
+            .opt("-reset_timestamps", "1")?
+            .flag("-y")
+            .output(output_pattern)?;
+        spawn("ffmpeg", args, cancel, timeout).await?;
 
         Ok(())
     }
@@ -73,7 +182,7 @@ impl FFMpeg {
 
 #[cfg(test)]
 mod tests {
-    use crate::dl::ffmpeg::FFMpeg;
+    use crate::dl::ffmpeg::{AudioTags, FFMpeg};
 
     #[test]
     fn round_mp3_bitrate() {
@@ -88,4 +197,43 @@ mod tests {
         assert_eq!(FFMpeg::round_mp3_bitrate(319.4), 320);
         assert_eq!(FFMpeg::round_mp3_bitrate(999.99), 320);
     }
+
+    #[test]
+    fn audio_args() {
+        let tags = AudioTags {
+            title: "Song",
+            artist: Some("Artist"),
+        };
+        let args = FFMpeg::audio_args("a.webm", Some("cover.jpg"), &tags)
+            .unwrap()
+            .build();
+        assert_eq!(
+            args,
+            vec![
+                "-i",
+                "a.webm",
+                "-i",
+                "cover.jpg",
+                "-map",
+                "0:a:0",
+                "-map",
+                "1:0",
+                "-c:v",
+                "mjpeg",
+                "-disposition:v:0",
+                "attached_pic",
+                "-metadata",
+                "title=Song",
+                "-metadata",
+                "artist=Artist",
+            ]
+        );
+
+        let tags = AudioTags {
+            title: "Song",
+            artist: None,
+        };
+        let args = FFMpeg::audio_args("a.webm", None, &tags).unwrap().build();
+        assert_eq!(args, vec!["-i", "a.webm", "-metadata", "title=Song"]);
+    }
 }
@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use crate::util::{parse_env_opt, parse_env_or};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub info_timeout: Duration,
+    pub download_timeout: Duration,
+    pub merge_timeout: Duration,
+    // seconds
+    pub max_duration: Option<u32>,
+    // bytes
+    pub max_filesize: Option<u64>,
+    // bytes, 50 MB on cloud Bot API, 2 GB on local telegram-bot-api server
+    pub max_upload_size: u64,
+    // images and videos in one post, carousels and slideshows
+    pub max_gallery_items: usize,
+    // entries of one /playlist, admins aren't limited
+    pub max_playlist_items: usize,
+}
+
+impl Limits {
+    pub fn from_env() -> Self {
+        Self {
+            info_timeout: Duration::from_secs(parse_env_or("DL_INFO_TIMEOUT", 60)),
+            download_timeout: Duration::from_secs(parse_env_or("DL_DOWNLOAD_TIMEOUT", 1800)),
+            merge_timeout: Duration::from_secs(parse_env_or("DL_MERGE_TIMEOUT", 600)),
+            max_duration: parse_env_opt("DL_MAX_DURATION"),
+            max_filesize: parse_env_opt("DL_MAX_FILESIZE"),
+            max_upload_size: parse_env_or("DL_MAX_UPLOAD_SIZE", 50 * 1024 * 1024),
+            max_gallery_items: parse_env_or("DL_MAX_GALLERY_ITEMS", 20),
+            max_playlist_items: parse_env_or("DL_MAX_PLAYLIST_ITEMS", 25),
+        }
+    }
+
+    pub fn check_duration(&self, duration: Option<f32>) -> bool {
+        match (self.max_duration, duration) {
+            (Some(max), Some(duration)) => duration <= max as f32,
+            _ => true,
+        }
+    }
+
+    pub fn check_filesize(&self, filesize: Option<u64>) -> bool {
+        match (self.max_filesize, filesize) {
+            (Some(max), Some(filesize)) => filesize <= max,
+            _ => true,
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::cmp::Reverse;
+use std::fmt;
+
+use crate::util::{parse_env_opt, parse_env_or};
+
+// Rules for picking formats when user didn't pick one. Containers and codecs are
+// preferences in order, formats not in the list still can be picked, just last
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatPolicy {
+    pub max_height: u16,
+    pub containers: Vec<String>,
+    pub vcodecs: Vec<String>,
+    pub acodecs: Vec<String>,
+    // kbps
+    pub max_vbr: Option<f32>,
+    pub max_fps: Option<f32>,
+}
+
+impl Default for FormatPolicy {
+    fn default() -> Self {
+        Self {
+            max_height: 1080,
+            containers: vec!["mp4".to_string()],
+            // h264 plays inline everywhere, including iOS
+            vcodecs: vec!["avc1".to_string()],
+            acodecs: Vec::new(),
+            max_vbr: None,
+            max_fps: None,
+        }
+    }
+}
+
+impl fmt::Display for FormatPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn opt(value: Option<f32>) -> String {
+            value.map_or("-".to_string(), |v| v.to_string())
+        }
+
+        write!(
+            f,
+            "height {}\ncontainers {}\nvcodecs {}\nacodecs {}\nvbr {}\nfps {}",
+            self.max_height,
+            self.containers.join(","),
+            self.vcodecs.join(","),
+            self.acodecs.join(","),
+            opt(self.max_vbr),
+            opt(self.max_fps)
+        )
+    }
+}
+
+// "mp4, webm" -> ["mp4", "webm"]
+pub fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|item| item.trim().to_lowercase())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+// position in preference list, lower is better. codecs are matched by prefix,
+// since yt-dlp reports them with profile, like avc1.640028
+fn preference(list: &[String], value: Option<&str>) -> Reverse<usize> {
+    let rank = value
+        .and_then(|value| {
+            list.iter()
+                .position(|item| value.starts_with(item.as_str()))
+        })
+        .unwrap_or(list.len());
+    Reverse(rank)
+}
+
+impl FormatPolicy {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            max_height: parse_env_or("DL_MAX_HEIGHT", default.max_height),
+            containers: parse_env_opt::<String>("DL_CONTAINERS")
+                .map_or(default.containers, |v| parse_list(&v)),
+            vcodecs: parse_env_opt::<String>("DL_VCODECS")
+                .map_or(default.vcodecs, |v| parse_list(&v)),
+            acodecs: parse_env_opt::<String>("DL_ACODECS")
+                .map_or(default.acodecs, |v| parse_list(&v)),
+            max_vbr: parse_env_opt("DL_MAX_VBR"),
+            max_fps: parse_env_opt("DL_MAX_FPS"),
+        }
+    }
+
+    pub fn allows_video(&self, height: u16, vbr: f32, fps: Option<f32>) -> bool {
+        height <= self.max_height
+            && match self.max_vbr {
+                Some(max) => vbr <= max,
+                None => true,
+            }
+            && match (self.max_fps, fps) {
+                (Some(max), Some(fps)) => fps <= max,
+                _ => true,
+            }
+    }
+
+    pub fn container_preference(&self, ext: &str) -> Reverse<usize> {
+        preference(&self.containers, Some(ext))
+    }
+
+    pub fn vcodec_preference(&self, vcodec: Option<&str>) -> Reverse<usize> {
+        preference(&self.vcodecs, vcodec)
+    }
+
+    pub fn acodec_preference(&self, acodec: Option<&str>) -> Reverse<usize> {
+        preference(&self.acodecs, acodec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_list, FormatPolicy};
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(parse_list(" MP4, webm,,"), vec!["mp4", "webm"]);
+        assert!(parse_list("").is_empty());
+    }
+
+    #[test]
+    fn test_preference() {
+        let policy = FormatPolicy {
+            vcodecs: parse_list("avc1,vp9"),
+            ..Default::default()
+        };
+        assert!(
+            policy.vcodec_preference(Some("avc1.640028")) > policy.vcodec_preference(Some("vp9"))
+        );
+        assert!(
+            policy.vcodec_preference(Some("vp9")) > policy.vcodec_preference(Some("av01.0.08M.08"))
+        );
+        assert_eq!(
+            policy.vcodec_preference(Some("av01")),
+            policy.vcodec_preference(None)
+        );
+    }
+
+    #[test]
+    fn test_allows_video() {
+        let policy = FormatPolicy {
+            max_height: 720,
+            max_vbr: Some(2000.0),
+            max_fps: Some(30.0),
+            ..Default::default()
+        };
+        assert!(policy.allows_video(720, 1500.0, Some(30.0)));
+        assert!(policy.allows_video(480, 1500.0, None));
+        assert!(!policy.allows_video(1080, 1500.0, Some(30.0)));
+        assert!(!policy.allows_video(720, 2500.0, Some(30.0)));
+        assert!(!policy.allows_video(720, 1500.0, Some(60.0)));
+    }
+}
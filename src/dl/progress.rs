@@ -0,0 +1,60 @@
+use regex::Regex;
+use std::sync::OnceLock;
+use tokio::sync::watch;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Progress {
+    FetchingInfo,
+    // gallery item being downloaded and total number of items
+    Item(usize, usize),
+    Video(f32),
+    Audio(f32),
+    Merging,
+    Converting,
+    Encoding,
+    Splitting,
+    Uploading,
+}
+
+pub type ProgressSender = watch::Sender<Progress>;
+pub type ProgressReceiver = watch::Receiver<Progress>;
+
+pub fn progress_channel() -> (ProgressSender, ProgressReceiver) {
+    watch::channel(Progress::FetchingInfo)
+}
+
+// [download]  45.3% of ~  10.00MiB at    1.00MiB/s ETA 00:05
+const RE_PROGRESS: &str = r"^\[download\]\s+(\d+(?:\.\d+)?)%";
+
+// called for every line of yt-dlp output, so regex is compiled once
+static PROGRESS_REGEX: OnceLock<Regex> = OnceLock::new();
+
+pub fn parse_progress(line: &str) -> Option<f32> {
+    let re = PROGRESS_REGEX.get_or_init(|| Regex::new(RE_PROGRESS).unwrap());
+    re.captures(line)?.get(1)?.as_str().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_progress;
+
+    #[test]
+    fn test_parse_progress() {
+        assert_eq!(
+            parse_progress("[download]  45.3% of ~  10.00MiB at    1.00MiB/s ETA 00:05"),
+            Some(45.3)
+        );
+        assert_eq!(
+            parse_progress("[download] 100% of   10.00MiB in 00:00:10 at 1.00MiB/s"),
+            Some(100.0)
+        );
+        assert_eq!(
+            parse_progress("[download] Destination: /tmp/00000000000_video.mp4"),
+            None
+        );
+        assert_eq!(
+            parse_progress("[youtube] 00000000000: Downloading webpage"),
+            None
+        );
+    }
+}
@@ -1,14 +1,22 @@
 use core::fmt;
-use std::process::Output;
+use std::process::Stdio;
 use std::str::Utf8Error;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
 use tokio::process::Command;
 use tracing::{event, Level};
 
+use super::args::{ArgError, Args};
+use super::cancel::CancelToken;
+
 #[derive(Debug)]
 pub enum SpawnError {
     CommandError(std::io::Error),
     UtfError(Utf8Error),
+    ArgError(ArgError),
     ErrorMessage(String),
+    Cancelled,
+    Timeout,
 }
 
 impl From<std::io::Error> for SpawnError {
@@ -23,31 +31,107 @@ impl From<Utf8Error> for SpawnError {
     }
 }
 
+impl From<ArgError> for SpawnError {
+    fn from(value: ArgError) -> Self {
+        Self::ArgError(value)
+    }
+}
+
 impl fmt::Display for SpawnError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use SpawnError as FE;
         match self {
             FE::CommandError(e) => write!(f, "Command::new - {}", e),
             FE::UtfError(_) => write!(f, "Error while decoding UTF8"),
+            FE::ArgError(e) => write!(f, "{}", e),
             FE::ErrorMessage(msg) => write!(f, "ffmpeg error - {}", msg),
+            FE::Cancelled => write!(f, "cancelled"),
+            FE::Timeout => write!(f, "timed out"),
         }
     }
 }
 
-/* !!! The argument list could be exploited in a way to inject malicious arguments !!!
-!!! and alter the way program executes and/or gain access to system             !!! */
-pub async fn spawn(program: &str, args: &[&str]) -> Result<Output, SpawnError> {
-    {
-        let cmd_args = args.join(" ");
-        event!(Level::INFO, "{} {}", program, cmd_args);
-    }
+fn log_command(program: &str, args: &[String]) {
+    let cmd_args = args.join(" ");
+    event!(Level::INFO, "{} {}", program, cmd_args);
+}
 
-    let output = Command::new(program).args(args).output().await?;
+// arguments are only accepted through Args builder to prevent option injection
+pub async fn spawn(
+    program: &str,
+    args: Args,
+    cancel: &CancelToken,
+    timeout: Duration,
+) -> Result<String, SpawnError> {
+    let mut stdout = String::new();
+    spawn_lines(program, args, cancel, timeout, |line| {
+        stdout.push_str(line);
+        stdout.push('\n');
+    })
+    .await?;
+
+    Ok(stdout)
+}
+
+// streaming variant of spawn, calls on_line for every line of stdout.
+// the child gets killed if cancel token fires or timeout expires before it exits
+pub async fn spawn_lines<F>(
+    program: &str,
+    args: Args,
+    cancel: &CancelToken,
+    timeout: Duration,
+    mut on_line: F,
+) -> Result<(), SpawnError>
+where
+    F: FnMut(&str),
+{
+    let args = args.build();
+    log_command(program, &args);
+
+    let mut child = Command::new(program)
+        .args(&args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+
+    let mut error = Vec::new();
+    let run = async {
+        let read_stdout = async {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Some(line) = lines.next_line().await? {
+                on_line(&line);
+            }
+
+            Ok::<(), std::io::Error>(())
+        };
+        let (stdout_res, stderr_res) = tokio::join!(read_stdout, stderr.read_to_end(&mut error));
+        stdout_res?;
+        stderr_res?;
+
+        child.wait().await
+    };
+
+    let status = tokio::select! {
+        status = run => Ok(status?),
+        _ = cancel.cancelled() => Err(SpawnError::Cancelled),
+        _ = tokio::time::sleep(timeout) => Err(SpawnError::Timeout),
+    };
+    let status = match status {
+        Ok(status) => status,
+        Err(e) => {
+            event!(Level::WARN, "killing {} - {}", program, e);
+            child.kill().await?;
+            return Err(e);
+        }
+    };
 
-    if !output.status.success() {
-        let message = std::str::from_utf8(&output.stderr)?;
+    if !status.success() {
+        let message = std::str::from_utf8(&error)?;
         return Err(SpawnError::ErrorMessage(message.to_string()));
     }
 
-    Ok(output)
+    Ok(())
 }
@@ -0,0 +1,181 @@
+use std::fs;
+use std::path::Path;
+use tracing::{event, Level};
+
+use super::ffmpeg::FFMpeg;
+use super::progress::Progress;
+use super::{delete_if_exists, DownloadContext, DownloadError};
+
+// audio bitrate of re-encoded video
+const REENCODE_ABR: u16 = 128;
+// below that video bitrate picture gets too bad, splitting is better
+const MIN_REENCODE_VBR: u32 = 500;
+
+fn file_size(path: &str) -> Result<u64, DownloadError> {
+    fs::metadata(path)
+        .map(|metadata| metadata.len())
+        .map_err(|e| DownloadError::Message(e.to_string()))
+}
+
+// /dir/id_.mp4 + part%03d -> /dir/id__part%03d.mp4
+fn with_suffix(path: &str, suffix: &str) -> Result<String, DownloadError> {
+    let path = Path::new(path);
+    let stem = path.file_stem().and_then(|stem| stem.to_str());
+    let ext = path.extension().and_then(|ext| ext.to_str());
+    match (stem, ext) {
+        (Some(stem), Some(ext)) => path
+            .with_file_name(format!("{}_{}.{}", stem, suffix, ext))
+            .into_os_string()
+            .into_string()
+            .map_err(|_| DownloadError::MakePathError),
+        _ => Err(DownloadError::MakePathError),
+    }
+}
+
+// video bitrate in kbps to fit whole duration into max_size,
+// with some room left for container overhead
+fn reencode_bitrate(max_size: u64, duration: f32, abr: u16) -> Option<u32> {
+    if duration <= 0.0 {
+        return None;
+    }
+
+    let total = max_size as f64 * 8.0 * 0.95 / 1000.0 / duration as f64;
+    let vbr = total - abr as f64;
+    if vbr < MIN_REENCODE_VBR as f64 {
+        None
+    } else {
+        Some(vbr as u32)
+    }
+}
+
+// segment length in seconds, so each part stays under max_size.
+// ffmpeg cuts on keyframes only, so parts are made a bit shorter
+fn segment_time(max_size: u64, size: u64, duration: f32) -> u32 {
+    let time = duration as f64 * max_size as f64 / size as f64 * 0.9;
+    (time as u32).max(1)
+}
+
+fn list_parts(pattern: &str) -> Result<Vec<String>, DownloadError> {
+    let pattern = Path::new(pattern);
+    let prefix = match pattern.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name.split('%').next().unwrap_or(name).to_string(),
+        None => return Err(DownloadError::MakePathError),
+    };
+    let dir = pattern.parent().ok_or(DownloadError::MakePathError)?;
+    let entries = fs::read_dir(dir).map_err(|e| DownloadError::Message(e.to_string()))?;
+
+    let mut parts: Vec<String> = entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+        .filter_map(|entry| entry.path().into_os_string().into_string().ok())
+        .collect();
+    parts.sort();
+
+    Ok(parts)
+}
+
+// makes sure video can be uploaded to Telegram: re-encodes it with lower bitrate
+// or, if that would look too bad, splits it into parts. returns paths in order
+pub async fn fit_upload(
+    ctx: &DownloadContext,
+    path: String,
+    duration: Option<f32>,
+) -> Result<Vec<String>, DownloadError> {
+    let max_size = ctx.limits.max_upload_size;
+    let size = file_size(&path)?;
+    if size <= max_size {
+        return Ok(vec![path]);
+    }
+
+    let duration = match duration {
+        Some(duration) => duration,
+        None => return Err(DownloadError::TooLarge),
+    };
+    event!(
+        Level::INFO,
+        "{} is {} bytes, over upload limit of {}",
+        path,
+        size,
+        max_size
+    );
+
+    let path = match reencode_bitrate(max_size, duration, REENCODE_ABR) {
+        Some(vbr) => {
+            ctx.progress.send_replace(Progress::Encoding);
+            let output_path = with_suffix(&path, "encoded")?;
+            FFMpeg::reencode_video(
+                path.as_str(),
+                output_path.as_str(),
+                vbr,
+                REENCODE_ABR,
+                &ctx.cancel,
+                ctx.limits.merge_timeout,
+            )
+            .await?;
+            delete_if_exists(&path);
+
+            if file_size(&output_path)? <= max_size {
+                return Ok(vec![output_path]);
+            }
+            output_path
+        }
+        None => path,
+    };
+
+    ctx.progress.send_replace(Progress::Splitting);
+    let size = file_size(&path)?;
+    let pattern = with_suffix(&path, "part%03d")?;
+    FFMpeg::split(
+        path.as_str(),
+        pattern.as_str(),
+        segment_time(max_size, size, duration),
+        &ctx.cancel,
+        ctx.limits.merge_timeout,
+    )
+    .await?;
+    delete_if_exists(&path);
+
+    let parts = list_parts(&pattern)?;
+    if parts.is_empty() {
+        return Err(DownloadError::Message("no parts after split".to_string()));
+    }
+    for part in &parts {
+        if file_size(part)? > max_size {
+            event!(Level::ERROR, "part {} is still over upload limit", part);
+            return Err(DownloadError::TooLarge);
+        }
+    }
+
+    Ok(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{reencode_bitrate, segment_time, with_suffix};
+
+    const MB: u64 = 1000 * 1000;
+
+    #[test]
+    fn test_reencode_bitrate() {
+        // 50 MB in 5 minutes is ~1266 kbps total
+        assert_eq!(reencode_bitrate(50 * MB, 300.0, 128), Some(1138));
+        // 50 MB in an hour is too little for video
+        assert_eq!(reencode_bitrate(50 * MB, 3600.0, 128), None);
+        assert_eq!(reencode_bitrate(50 * MB, 0.0, 128), None);
+    }
+
+    #[test]
+    fn test_segment_time() {
+        assert_eq!(segment_time(50 * MB, 200 * MB, 3600.0), 810);
+        assert_eq!(segment_time(50 * MB, 100 * MB * 1000, 1.0), 1);
+    }
+
+    #[test]
+    fn test_with_suffix() {
+        assert_eq!(
+            with_suffix("/tmp/job_1/abc_.mp4", "part%03d").ok(),
+            Some("/tmp/job_1/abc__part%03d.mp4".to_string())
+        );
+        assert!(with_suffix("/tmp/job_1/abc", "encoded").is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::{event, Level};
+
+use crate::util::parse_env_or;
+
+const JOB_PREFIX: &str = "job_";
+
+// older versions downloaded straight into temp dir as <id>_.mp4
+fn is_stray_download(name: &str) -> bool {
+    name.len() > "_.mp4".len() && name.ends_with("_.mp4")
+}
+
+fn list_dir(dir: &Path) -> Vec<(PathBuf, String)> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    entries
+        .flatten()
+        .filter_map(|entry| Some((entry.path(), entry.file_name().into_string().ok()?)))
+        .collect()
+}
+
+fn remove(path: &Path, res: io::Result<()>) {
+    match res {
+        Ok(()) => event!(Level::INFO, "swept {}", path.display()),
+        Err(e) => event!(Level::ERROR, "sweep {} error {}", path.display(), e),
+    }
+}
+
+// Per-job directory for download files. Everything inside is removed on drop,
+// so files don't leak no matter how the pipeline exits
+pub struct Workspace {
+    dir: PathBuf,
+}
+
+impl Workspace {
+    pub fn root() -> PathBuf {
+        parse_env_or("DL_WORKSPACE_ROOT", std::env::temp_dir().join("mk-dl-bot"))
+    }
+
+    pub fn create(name: &str) -> io::Result<Self> {
+        let dir = Self::root().join(name);
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        fs::create_dir_all(&dir)?;
+
+        Ok(Self { dir })
+    }
+
+    pub fn for_job(id: i32) -> io::Result<Self> {
+        Self::create(&format!("{}{}", JOB_PREFIX, id))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // removes job workspaces and stray downloads left over from previous runs.
+    // root may be shared with other data, so only what we created is touched
+    pub fn sweep() {
+        for (path, name) in list_dir(&Self::root()) {
+            if path.is_dir() && name.starts_with(JOB_PREFIX) {
+                remove(&path, fs::remove_dir_all(&path));
+            }
+        }
+
+        for (path, name) in list_dir(&std::env::temp_dir()) {
+            if path.is_file() && is_stray_download(&name) {
+                remove(&path, fs::remove_file(&path));
+            }
+        }
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            event!(
+                Level::ERROR,
+                "failed to remove workspace {} - {}",
+                self.dir.display(),
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_stray_download, Workspace};
+    use std::fs;
+
+    #[test]
+    fn test_workspace_removed_on_drop() {
+        let workspace = Workspace::create("test_workspace_removed_on_drop").unwrap();
+        let dir = workspace.dir().to_path_buf();
+        fs::write(dir.join("video.mp4"), b"video").unwrap();
+        assert!(dir.join("video.mp4").exists());
+
+        drop(workspace);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_is_stray_download() {
+        assert!(is_stray_download("dQw4w9WgXcQ_.mp4"));
+        assert!(!is_stray_download("_.mp4"));
+        assert!(!is_stray_download("dQw4w9WgXcQ_video.mp4"));
+        assert!(!is_stray_download("holiday.mp4"));
+    }
+}
@@ -1,9 +1,17 @@
-use super::spawn::{spawn, SpawnError};
+use super::args::{ArgError, Args};
+use super::cancel::CancelToken;
+use super::clip::Clip;
+use super::policy::FormatPolicy;
+use super::progress::parse_progress;
+use super::spawn::{spawn, spawn_lines, SpawnError};
 use core::fmt;
 use ordered_float::OrderedFloat;
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
 use serde_json;
+use std::cmp::Reverse;
 use std::fs;
+use std::path::Path;
+use std::time::Duration;
 use tracing::{event, Level};
 
 #[derive(Deserialize, Debug)]
@@ -18,6 +26,16 @@ pub struct YtDlpFormat {
     pub acodec: Option<String>,
     pub vbr: Option<f32>,
     pub abr: Option<f32>,
+    pub fps: Option<f32>,
+    pub filesize: Option<f64>,
+    pub filesize_approx: Option<f64>,
+}
+
+// video height and container picked by user, -> 1080p mp4
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoQuality {
+    pub height: u16,
+    pub ext: String,
 }
 
 struct VideoFormat<'a> {
@@ -29,10 +47,6 @@ struct VideoFormat<'a> {
 }
 
 impl<'a> VideoFormat<'a> {
-    pub fn is_mp4(&self) -> bool {
-        self.format.ext == "mp4"
-    }
-
     pub fn is_premium(&self) -> bool {
         self.format_note.contains("Premium")
     }
@@ -54,6 +68,12 @@ impl YtDlpFormat {
         }
     }
 
+    pub fn approx_filesize(&self) -> Option<u64> {
+        self.filesize
+            .or(self.filesize_approx)
+            .map(|size| size as u64)
+    }
+
     fn str_option<T>(opt: &Option<T>) -> String
     where
         T: ToString,
@@ -83,30 +103,123 @@ impl fmt::Display for YtDlpFormat {
     }
 }
 
+const IMAGE_EXTS: [&str; 4] = ["jpg", "jpeg", "png", "webp"];
+
+// these return posts with several photos and videos as playlists
+const GALLERY_EXTRACTORS: [&str; 6] =
+    ["Instagram", "Twitter", "TikTok", "Reddit", "VK", "Facebook"];
+
+// flat playlist entries may come with null title
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::deserialize(deserializer)?.unwrap_or_default())
+}
+
 #[derive(Deserialize, Debug)]
 pub struct YtDlpInfo {
     pub id: String,
+    pub extractor_key: Option<String>,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub title: String,
+    pub duration: Option<f32>,
+    pub uploader: Option<String>,
+    pub webpage_url: Option<String>,
+    pub thumbnail: Option<String>,
+    pub artist: Option<String>,
+    pub track: Option<String>,
+    // playlists and galleries have entries instead of formats
+    #[serde(default)]
     pub formats: Vec<YtDlpFormat>,
+    #[serde(rename = "_type")]
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub entries: Vec<YtDlpInfo>,
+    pub playlist_index: Option<usize>,
 }
 
 impl YtDlpInfo {
-    const H_LIMIT: u16 = 1080;
-
     pub fn parse(json: &[u8]) -> Result<YtDlpInfo, serde_json::Error> {
         let mut info: YtDlpInfo = serde_json::from_slice(json)?;
-        for format in &mut info.formats {
+        info.process();
+
+        Ok(info)
+    }
+
+    fn process(&mut self) {
+        for format in &mut self.formats {
             format.process()
         }
+        for entry in &mut self.entries {
+            entry.process()
+        }
+    }
 
-        Ok(info)
+    pub fn has_video(&self) -> bool {
+        self.formats.iter().any(|f| f.vcodec.is_some())
+    }
+
+    // images come as formats without any codecs, biggest one is the original
+    pub fn best_image_format(&self) -> Option<&YtDlpFormat> {
+        self.formats
+            .iter()
+            .filter(|f| {
+                f.vcodec.is_none() && f.acodec.is_none() && IMAGE_EXTS.contains(&f.ext.as_str())
+            })
+            .max_by_key(|f| f.width.unwrap_or(0) as u32 * f.height.unwrap_or(0) as u32)
+    }
+
+    fn is_playlist_kind(&self) -> bool {
+        self.kind.as_deref() == Some("playlist")
+    }
+
+    // flat playlist entry that only points to a video
+    fn is_reference(&self) -> bool {
+        matches!(self.kind.as_deref(), Some("url" | "url_transparent"))
+    }
+
+    // separate video and audio streams are how full videos come, not parts of a post
+    fn has_split_video(&self) -> bool {
+        self.formats
+            .iter()
+            .any(|f| f.vcodec.is_some() && f.acodec.is_none())
+    }
+
+    // carousel, slideshow or a post with a single image
+    pub fn is_gallery(&self) -> bool {
+        if self.is_playlist_kind() {
+            if self.entries.is_empty() || self.entries.iter().any(|e| e.is_reference()) {
+                return false;
+            }
+            let known = self
+                .extractor_key
+                .as_deref()
+                .is_some_and(|key| GALLERY_EXTRACTORS.contains(&key));
+            return known || !self.entries.iter().any(|e| e.has_split_video());
+        }
+        !self.has_video() && self.best_image_format().is_some()
+    }
+
+    // separate videos, those are downloaded with /playlist
+    pub fn is_playlist(&self) -> bool {
+        self.is_playlist_kind() && !self.entries.is_empty() && !self.is_gallery()
     }
 
-    pub fn default_format(&self) -> Option<&YtDlpFormat> {
+    pub fn gallery_entries(&self) -> Vec<&YtDlpInfo> {
+        if self.entries.is_empty() {
+            vec![self]
+        } else {
+            self.entries.iter().collect()
+        }
+    }
+
+    pub fn default_format(&self, policy: &FormatPolicy) -> Option<&YtDlpFormat> {
         match self
             .formats
             .iter()
-            .filter(|f| f.height.is_some_and(|h| h <= Self::H_LIMIT))
+            .filter(|f| f.height.is_some_and(|h| h <= policy.max_height))
             .last()
         {
             Some(format) => Some(format),
@@ -146,7 +259,7 @@ impl YtDlpInfo {
         }
     }
 
-    pub fn best_audio_format(&self) -> Option<&YtDlpFormat> {
+    pub fn best_audio_format(&self, policy: &FormatPolicy) -> Option<&YtDlpFormat> {
         let format = self
             .formats
             .iter()
@@ -156,7 +269,12 @@ impl YtDlpInfo {
                     abr: f.abr?,
                 })
             })
-            .max_by_key(|f| OrderedFloat(f.abr));
+            .max_by_key(|f| {
+                (
+                    policy.acodec_preference(f.format.acodec.as_deref()),
+                    OrderedFloat(f.abr),
+                )
+            });
 
         match format {
             Some(af) => Some(af.format),
@@ -167,21 +285,70 @@ impl YtDlpInfo {
         }
     }
 
-    pub fn best_video_format(&self) -> Option<&YtDlpFormat> {
-        let format = self
-            .formats
-            .iter()
-            .filter_map(|f| {
-                Some(VideoFormat {
-                    format: f,
-                    format_note: f.format_note.as_ref()?,
-                    width: f.width?,
-                    height: f.height?,
-                    vbr: f.vbr?,
-                })
+    fn all_video_formats(&self) -> impl Iterator<Item = VideoFormat<'_>> {
+        self.formats.iter().filter_map(|f| {
+            Some(VideoFormat {
+                format: f,
+                format_note: f.format_note.as_ref()?,
+                width: f.width?,
+                height: f.height?,
+                vbr: f.vbr?,
             })
-            .filter(|f| f.height <= Self::H_LIMIT && f.is_mp4() && !f.is_premium())
-            .max_by_key(|f| OrderedFloat(f.vbr));
+        })
+    }
+
+    // formats allowed by policy, pick from them with video_preference
+    fn video_formats(&self, policy: &FormatPolicy) -> Vec<VideoFormat<'_>> {
+        self.all_video_formats()
+            .filter(|f| !f.is_premium() && policy.allows_video(f.height, f.vbr, f.format.fps))
+            .collect()
+    }
+
+    fn video_preference(
+        policy: &FormatPolicy,
+        f: &VideoFormat<'_>,
+    ) -> (Reverse<usize>, Reverse<usize>, OrderedFloat<f32>) {
+        (
+            policy.vcodec_preference(f.format.vcodec.as_deref()),
+            policy.container_preference(&f.format.ext),
+            OrderedFloat(f.vbr),
+        )
+    }
+
+    // one best format per height and container, highest first
+    pub fn video_qualities(&self) -> Vec<(VideoQuality, &YtDlpFormat)> {
+        let mut qualities: Vec<(VideoQuality, &YtDlpFormat)> = Vec::new();
+        for vf in self.all_video_formats().filter(|f| !f.is_premium()) {
+            let quality = VideoQuality {
+                height: vf.height,
+                ext: vf.format.ext.clone(),
+            };
+            match qualities.iter_mut().find(|(q, _)| *q == quality) {
+                Some((_, format)) => {
+                    if format.vbr.is_some_and(|vbr| vbr < vf.vbr) {
+                        *format = vf.format;
+                    }
+                }
+                None => qualities.push((quality, vf.format)),
+            }
+        }
+
+        qualities.sort_by(|(a, _), (b, _)| b.height.cmp(&a.height).then(a.ext.cmp(&b.ext)));
+        qualities
+    }
+
+    pub fn video_format_for(&self, quality: &VideoQuality) -> Option<&YtDlpFormat> {
+        self.video_qualities()
+            .into_iter()
+            .find(|(q, _)| q == quality)
+            .map(|(_, format)| format)
+    }
+
+    pub fn best_video_format(&self, policy: &FormatPolicy) -> Option<&YtDlpFormat> {
+        let format = self
+            .video_formats(policy)
+            .into_iter()
+            .max_by_key(|f| Self::video_preference(policy, f));
 
         match format {
             Some(vf) => Some(vf.format),
@@ -191,6 +358,51 @@ impl YtDlpInfo {
             }
         }
     }
+
+    // best video format with known size not exceeding max_size
+    pub fn best_video_format_within(
+        &self,
+        policy: &FormatPolicy,
+        max_size: u64,
+    ) -> Option<&YtDlpFormat> {
+        self.video_formats(policy)
+            .into_iter()
+            .filter(|f| {
+                f.format
+                    .approx_filesize()
+                    .is_some_and(|size| size <= max_size)
+            })
+            .max_by_key(|f| Self::video_preference(policy, f))
+            .map(|f| f.format)
+    }
+}
+
+// gallery entry and time range to download, whole single video if neither is set
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DownloadPart {
+    pub item: Option<usize>,
+    pub clip: Option<Clip>,
+}
+
+// --flat-playlist output, entries are just links without formats
+#[derive(Deserialize, Debug)]
+pub struct YtDlpPlaylist {
+    pub id: String,
+    pub title: Option<String>,
+    #[serde(default)]
+    pub entries: Vec<YtDlpPlaylistEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct YtDlpPlaylistEntry {
+    // missing for some entries, those are skipped
+    pub url: Option<String>,
+}
+
+impl YtDlpPlaylist {
+    pub fn parse(json: &[u8]) -> Result<YtDlpPlaylist, serde_json::Error> {
+        serde_json::from_slice(json)
+    }
 }
 
 #[derive(Debug)]
@@ -200,6 +412,7 @@ pub enum YtDlpError {
     JsonError,
     NoFormats,
     NoFilePresent,
+    Timeout,
 }
 // ^(?:ERROR: \[.*\] \S* )(.*$) - regex for matching yt-dlp's youtube errors
 
@@ -207,11 +420,18 @@ impl From<SpawnError> for YtDlpError {
     fn from(value: SpawnError) -> Self {
         match value {
             SpawnError::ErrorMessage(msg) => Self::ErrorMessage(msg),
+            SpawnError::Timeout => Self::Timeout,
             _ => Self::SpawnError(value),
         }
     }
 }
 
+impl From<ArgError> for YtDlpError {
+    fn from(value: ArgError) -> Self {
+        Self::SpawnError(SpawnError::ArgError(value))
+    }
+}
+
 impl From<serde_json::Error> for YtDlpError {
     fn from(_value: serde_json::Error) -> Self {
         Self::JsonError
@@ -227,39 +447,99 @@ impl fmt::Display for YtDlpError {
             YTE::JsonError => write!(f, "json parsing error"),
             YTE::NoFormats => write!(f, "no formats were parsed"),
             YTE::NoFilePresent => write!(f, "downloaded file doesn't exists"),
+            YTE::Timeout => write!(f, "yt-dlp timed out"),
         }
     }
 }
 
 pub struct YtDlp {}
 
-// BUG: REAL ARGUMENT INJECTION! FIX ASAP
 impl YtDlp {
-    pub async fn load_info(url: &str) -> Result<YtDlpInfo, YtDlpError> {
-        let output = spawn("python", &["-m", "yt_dlp", url, "-j"]).await?;
+    fn args() -> Result<Args, ArgError> {
+        Args::new().opt("-m", "yt_dlp")
+    }
 
-        let info = YtDlpInfo::parse(&output.stdout)?;
-        if info.formats.is_empty() {
+    // gallery entries are downloaded one by one from the same url
+    fn item_args(args: Args, item: Option<usize>) -> Result<Args, ArgError> {
+        match item {
+            Some(item) => args.opt("--playlist-items", &item.to_string()),
+            None => Ok(args.flag("--no-playlist")),
+        }
+    }
+
+    // one more entry than max_entries is loaded, so caller can tell there are too many
+    pub async fn load_info(
+        url: &str,
+        max_entries: usize,
+        cancel: &CancelToken,
+        timeout: Duration,
+    ) -> Result<YtDlpInfo, YtDlpError> {
+        let args = Self::args()?
+            .flag("-J")
+            .flag("--no-playlist")
+            // real playlists stay unresolved, posts have their entries inline anyway
+            .flag("--flat-playlist")
+            .opt("--playlist-end", &(max_entries + 1).to_string())?
+            .url(url)?;
+        let output = spawn("python", args, cancel, timeout).await?;
+
+        let info = YtDlpInfo::parse(output.as_bytes())?;
+        if info.formats.is_empty() && info.entries.is_empty() {
             return Err(YtDlpError::NoFormats);
         }
 
         Ok(info)
     }
 
-    pub async fn download(url: &str, format_id: &str, output_path: &str) -> Result<(), YtDlpError> {
-        spawn(
-            "python",
-            &[
-                "-m",
-                "yt_dlp",
-                url,
-                "-f",
-                format_id,
-                "-o",
-                output_path,
-                "--force-overwrites",
-            ],
-        )
+    // items are 1-based and inclusive, like yt-dlp counts them
+    pub async fn load_playlist(
+        url: &str,
+        start: usize,
+        end: Option<usize>,
+        cancel: &CancelToken,
+        timeout: Duration,
+    ) -> Result<YtDlpPlaylist, YtDlpError> {
+        let mut args = Self::args()?
+            .flag("-J")
+            .flag("--flat-playlist")
+            .opt("--playlist-start", &start.to_string())?;
+        if let Some(end) = end {
+            args = args.opt("--playlist-end", &end.to_string())?;
+        }
+        let args = args.url(url)?;
+        let output = spawn("python", args, cancel, timeout).await?;
+
+        Ok(YtDlpPlaylist::parse(output.as_bytes())?)
+    }
+
+    pub async fn download<F>(
+        url: &str,
+        part: DownloadPart,
+        format_id: &str,
+        output_path: &str,
+        cancel: &CancelToken,
+        timeout: Duration,
+        mut on_progress: F,
+    ) -> Result<(), YtDlpError>
+    where
+        F: FnMut(f32),
+    {
+        let mut args = Self::item_args(Self::args()?, part.item)?
+            .opt("-f", format_id)?
+            .opt("-o", output_path)?
+            .flag("--force-overwrites")
+            .flag("--no-part")
+            .flag("--newline")
+            .flag("--progress");
+        if let Some(clip) = part.clip {
+            args = args.opt("--download-sections", &clip.section())?;
+        }
+        let args = args.url(url)?;
+        spawn_lines("python", args, cancel, timeout, |line| {
+            if let Some(percent) = parse_progress(line) {
+                on_progress(percent);
+            }
+        })
         .await?;
 
         match fs::metadata(output_path) {
@@ -267,40 +547,206 @@ impl YtDlp {
             Err(_) => Err(YtDlpError::NoFilePresent),
         }
     }
+
+    // thumbnail is converted to jpg, so output_path should have jpg extension
+    pub async fn download_thumbnail(
+        url: &str,
+        item: Option<usize>,
+        output_path: &str,
+        cancel: &CancelToken,
+        timeout: Duration,
+    ) -> Result<(), YtDlpError> {
+        // yt-dlp appends extension itself after conversion
+        let template = Path::new(output_path).with_extension("%(ext)s");
+        let args = Self::item_args(Self::args()?, item)?
+            .flag("--skip-download")
+            .flag("--write-thumbnail")
+            .opt("--convert-thumbnails", "jpg")?
+            .opt("-o", template.to_string_lossy().as_ref())?
+            .url(url)?;
+        spawn("python", args, cancel, timeout).await?;
+
+        match fs::metadata(output_path) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(YtDlpError::NoFilePresent),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::YtDlp;
+    use super::{VideoQuality, YtDlp, YtDlpInfo, YtDlpPlaylist};
+    use crate::dl::cancel::CancelToken;
+    use crate::dl::policy::{parse_list, FormatPolicy};
     use std::env;
+    use std::time::Duration;
+
+    async fn load_test_info() -> YtDlpInfo {
+        dotenv::from_filename(".env.test").unwrap();
+        YtDlp::load_info(
+            env::var("TEST_URL").unwrap().as_str(),
+            1,
+            &CancelToken::new(),
+            Duration::from_secs(60),
+        )
+        .await
+        .unwrap()
+    }
 
     #[tokio::test]
     async fn best_av_format() {
-        dotenv::from_filename(".env.test").unwrap();
-        let info = YtDlp::load_info(env::var("TEST_URL").unwrap().as_str())
-            .await
-            .unwrap();
+        let info = load_test_info().await;
         let video = info.best_av_format().unwrap();
         assert_eq!(video.format_id, "22");
     }
 
     #[tokio::test]
     async fn best_audio_format() {
-        dotenv::from_filename(".env.test").unwrap();
-        let info = YtDlp::load_info(env::var("TEST_URL").unwrap().as_str())
-            .await
-            .unwrap();
-        let video = info.best_audio_format().unwrap();
+        let info = load_test_info().await;
+        let video = info.best_audio_format(&FormatPolicy::default()).unwrap();
         assert_eq!(video.format_id, "140");
     }
 
     #[tokio::test]
     async fn best_video_format() {
-        dotenv::from_filename(".env.test").unwrap();
-        let info = YtDlp::load_info(env::var("TEST_URL").unwrap().as_str())
-            .await
-            .unwrap();
-        let video = info.best_video_format().unwrap();
+        let info = load_test_info().await;
+        let video = info.best_video_format(&FormatPolicy::default()).unwrap();
         assert_eq!(video.format_id, "137");
     }
+
+    #[test]
+    fn video_qualities() {
+        let json = br#"{"id": "test", "title": "test", "formats": [
+            {"format_id": "1", "format_note": "480p", "ext": "mp4", "width": 854, "height": 480, "vbr": 500},
+            {"format_id": "2", "format_note": "480p", "ext": "mp4", "width": 854, "height": 480, "vbr": 900},
+            {"format_id": "3", "format_note": "480p", "ext": "webm", "width": 854, "height": 480, "vbr": 700},
+            {"format_id": "4", "format_note": "1440p", "ext": "webm", "width": 2560, "height": 1440, "vbr": 9000},
+            {"format_id": "5", "format_note": "1080p Premium", "ext": "mp4", "width": 1920, "height": 1080, "vbr": 6000},
+            {"format_id": "6", "format_note": "medium", "ext": "m4a", "abr": 128}
+        ]}"#;
+        let info = YtDlpInfo::parse(json).unwrap();
+
+        let qualities = info.video_qualities();
+        let qualities: Vec<(u16, &str, &str)> = qualities
+            .iter()
+            .map(|(q, f)| (q.height, q.ext.as_str(), f.format_id.as_str()))
+            .collect();
+        assert_eq!(
+            qualities,
+            vec![(1440, "webm", "4"), (480, "mp4", "2"), (480, "webm", "3")]
+        );
+
+        let quality = VideoQuality {
+            height: 480,
+            ext: "mp4".to_string(),
+        };
+        assert_eq!(info.video_format_for(&quality).unwrap().format_id, "2");
+    }
+
+    #[test]
+    fn flat_playlist() {
+        let json = br#"{"id": "PL1", "title": "album", "_type": "playlist", "entries": [
+            {"_type": "url", "ie_key": "Youtube", "id": "a", "url": "https://www.youtube.com/watch?v=a", "title": "first"},
+            {"_type": "url", "ie_key": "Youtube", "id": "b", "url": "https://www.youtube.com/watch?v=b", "title": null},
+            {"_type": "url", "ie_key": "Youtube", "id": "c", "title": "no url"}
+        ]}"#;
+        let playlist = YtDlpPlaylist::parse(json).unwrap();
+        assert_eq!(playlist.title.as_deref(), Some("album"));
+        assert_eq!(playlist.entries.len(), 3);
+        assert_eq!(
+            playlist.entries[0].url.as_deref(),
+            Some("https://www.youtube.com/watch?v=a")
+        );
+        assert_eq!(playlist.entries[2].url, None);
+    }
+
+    #[test]
+    fn gallery() {
+        let json = br#"{"id": "post", "title": "post", "_type": "playlist", "entries": [
+            {"id": "1", "title": "1", "playlist_index": 1, "formats": [
+                {"format_id": "0", "ext": "jpg", "vcodec": "none", "acodec": "none", "width": 320, "height": 320},
+                {"format_id": "1", "ext": "jpg", "vcodec": "none", "acodec": "none", "width": 1080, "height": 1080}
+            ]},
+            {"id": "2", "title": "2", "playlist_index": 2, "formats": [
+                {"format_id": "2", "format_note": "720p", "ext": "mp4", "vcodec": "avc1", "acodec": "mp4a", "width": 720, "height": 1280, "vbr": 1500}
+            ]}
+        ]}"#;
+        let info = YtDlpInfo::parse(json).unwrap();
+        assert!(info.is_gallery());
+
+        let entries = info.gallery_entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].best_image_format().unwrap().format_id, "1");
+        assert_eq!(entries[1].playlist_index, Some(2));
+        assert!(entries[1].has_video());
+        assert!(entries[1].best_image_format().is_none());
+
+        let json = br#"{"id": "test", "title": "test", "formats": [
+            {"format_id": "1", "format_note": "720p", "ext": "mp4", "vcodec": "avc1", "width": 1280, "height": 720, "vbr": 1500},
+            {"format_id": "sb0", "ext": "mhtml", "vcodec": "none", "acodec": "none"}
+        ]}"#;
+        let info = YtDlpInfo::parse(json).unwrap();
+        assert!(!info.is_gallery());
+    }
+
+    #[test]
+    fn playlist_is_not_gallery() {
+        let json = br#"{"id": "PL1", "title": "album", "_type": "playlist", "extractor_key": "YoutubeTab", "entries": [
+            {"_type": "url", "ie_key": "Youtube", "id": "a", "url": "https://www.youtube.com/watch?v=a", "title": null}
+        ]}"#;
+        let info = YtDlpInfo::parse(json).unwrap();
+        assert!(!info.is_gallery());
+        assert!(info.is_playlist());
+
+        let resolved = |extractor: &str| {
+            format!(
+                r#"{{"id": "PL1", "title": "album", "_type": "playlist", "extractor_key": "{}", "entries": [
+                    {{"id": "a", "title": "a", "formats": [
+                        {{"format_id": "1", "ext": "mp4", "vcodec": "avc1", "acodec": "none", "width": 1280, "height": 720}},
+                        {{"format_id": "2", "ext": "m4a", "vcodec": "none", "acodec": "mp4a"}}
+                    ]}}
+                ]}}"#,
+                extractor
+            )
+        };
+        let info = YtDlpInfo::parse(resolved("YoutubeTab").as_bytes()).unwrap();
+        assert!(info.is_playlist());
+        let info = YtDlpInfo::parse(resolved("Instagram").as_bytes()).unwrap();
+        assert!(info.is_gallery());
+        assert!(!info.is_playlist());
+    }
+
+    #[test]
+    fn best_video_format_policy() {
+        let json = br#"{"id": "test", "title": "test", "formats": [
+            {"format_id": "1", "format_note": "720p", "ext": "mp4", "vcodec": "avc1.4d401f", "width": 1280, "height": 720, "vbr": 1500, "fps": 30},
+            {"format_id": "2", "format_note": "1080p", "ext": "mp4", "vcodec": "avc1.640028", "width": 1920, "height": 1080, "vbr": 3000, "fps": 30},
+            {"format_id": "3", "format_note": "1080p", "ext": "webm", "vcodec": "vp9", "width": 1920, "height": 1080, "vbr": 2500, "fps": 30},
+            {"format_id": "4", "format_note": "1080p60", "ext": "mp4", "vcodec": "avc1.64002a", "width": 1920, "height": 1080, "vbr": 4500, "fps": 60},
+            {"format_id": "5", "format_note": "1440p", "ext": "webm", "vcodec": "vp9", "width": 2560, "height": 1440, "vbr": 9000, "fps": 30}
+        ]}"#;
+        let info = YtDlpInfo::parse(json).unwrap();
+
+        let policy = FormatPolicy::default();
+        assert_eq!(info.best_video_format(&policy).unwrap().format_id, "4");
+
+        let policy = FormatPolicy {
+            max_fps: Some(30.0),
+            ..Default::default()
+        };
+        assert_eq!(info.best_video_format(&policy).unwrap().format_id, "2");
+
+        let policy = FormatPolicy {
+            max_height: 1440,
+            vcodecs: parse_list("vp9,avc1"),
+            ..Default::default()
+        };
+        assert_eq!(info.best_video_format(&policy).unwrap().format_id, "5");
+
+        let policy = FormatPolicy {
+            max_height: 720,
+            ..Default::default()
+        };
+        assert_eq!(info.best_video_format(&policy).unwrap().format_id, "1");
+    }
 }
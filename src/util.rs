@@ -13,3 +13,42 @@ where
 {
     str::parse(unwrap_env(name).as_str()).expect(format!("env '{}' parse error", name).as_str())
 }
+
+pub fn parse_env_opt<T>(name: &str) -> Option<T>
+where
+    T: FromStr,
+    T::Err: fmt::Debug,
+{
+    match env::var(name) {
+        Ok(value) => {
+            Some(str::parse(value.as_str()).expect(format!("env '{}' parse error", name).as_str()))
+        }
+        Err(_) => None,
+    }
+}
+
+pub fn parse_env_or<T>(name: &str, default: T) -> T
+where
+    T: FromStr,
+    T::Err: fmt::Debug,
+{
+    parse_env_opt(name).unwrap_or(default)
+}
+
+// limit override from a command, "default" resets it back to global value.
+// zero is valid only where it means something, like no limit for quotas
+pub fn parse_limit<T>(value: &str, allow_zero: bool) -> Option<Option<T>>
+where
+    T: FromStr + Default + PartialOrd,
+{
+    if value == "default" {
+        return Some(None);
+    }
+
+    match value.parse() {
+        Ok(limit) if limit > T::default() || (allow_zero && limit == T::default()) => {
+            Some(Some(limit))
+        }
+        _ => None,
+    }
+}